@@ -18,4 +18,84 @@ pub enum VaultError {
     Underflow,
     #[msg("Unauthorized: only vault authority can perform this action")]
     Unauthorized,
+    #[msg("Amount is too small to be worth a single vault share")]
+    ZeroShares,
+    #[msg("Failed to compute interest from the mint's interest bearing config")]
+    InterestCalculation,
+    #[msg("The mint authority has not been handed to the vault program")]
+    MintAuthorityNotDelegated,
+    #[msg("The rate authority has not been handed to the vault")]
+    RateAuthorityNotDelegated,
+    #[msg("The mint has no interest bearing config")]
+    NotInterestBearing,
+    #[msg("Interest rate is outside of the vault's rate bounds")]
+    RateOutOfBounds,
+    #[msg("Minimum rate must not exceed maximum rate")]
+    InvalidRateBounds,
+    #[msg("The rate change delay has not elapsed yet")]
+    RateChangeTimelocked,
+    #[msg("Rate change delay must not be negative")]
+    InvalidRateChangeDelay,
+    #[msg("Vault is paused")]
+    VaultPaused,
+    #[msg("Deposit would exceed the vault's total deposit cap")]
+    VaultDepositCapExceeded,
+    #[msg("Deposit would exceed the per-user deposit cap")]
+    UserDepositCapExceeded,
+    #[msg("Lock duration must be between zero and the maximum lock duration")]
+    InvalidLockDuration,
+    #[msg("Position is locked until its maturity")]
+    PositionLocked,
+    #[msg("Not enough liquidity in the vault reserve")]
+    InsufficientLiquidity,
+    #[msg("Withdrawal requests must be fulfilled in the order they were made")]
+    WithdrawalRequestOutOfOrder,
+    #[msg("Withdrawal request not found")]
+    WithdrawalRequestNotFound,
+    #[msg("Withdrawal request has not been fulfilled yet")]
+    WithdrawalNotFulfilled,
+    #[msg("Withdrawal cooldown has not elapsed yet")]
+    WithdrawalCooldown,
+    #[msg("Withdrawal cooldown must not be negative")]
+    InvalidWithdrawalCooldown,
+    #[msg("Fee must not exceed 1000 basis points")]
+    InvalidFee,
+    #[msg("No fee recipient has been set")]
+    FeeRecipientNotSet,
+    #[msg("Position still holds shares")]
+    PositionNotEmpty,
+    #[msg("Vault still has shares, queued withdrawals or uncollected fees")]
+    VaultNotEmpty,
+    #[msg("Signer is not the pending vault authority")]
+    NotPendingAuthority,
+    #[msg("Multisig needs 1 to 11 distinct signers and a threshold no larger than the signer set")]
+    InvalidMultisig,
+    #[msg("Not enough multisig signers approved the instruction")]
+    MultisigThresholdNotMet,
+    #[msg("Vault roles account is full")]
+    TooManyRoleMembers,
+    #[msg("Reserve holds fewer tokens than the vault accounts for")]
+    ReserveDeficit,
+    #[msg("No excess reserve tokens to recover")]
+    NoExcessReserve,
+    #[msg("Vault has been shut down")]
+    VaultShutdown,
+    #[msg("Vault has not been shut down")]
+    VaultNotShutdown,
+    #[msg("Beneficiary is not whitelisted for the vault's mint")]
+    BeneficiaryNotWhitelisted,
+    #[msg("Owner of the recipient token account is not whitelisted for the vault's mint")]
+    RecipientNotWhitelisted,
+    #[msg("Batch accounts do not match the batch amounts")]
+    InvalidBatch,
+    #[msg("Mint has a transfer hook but its accounts are missing or do not match")]
+    MissingTransferHookAccounts,
+    #[msg("Transfer fee exceeds the maximum fee")]
+    TransferFeeExceedsMax,
+    #[msg("Batch deposits do not support mints that charge a transfer fee")]
+    TransferFeeNotSupported,
+    #[msg("The fee increase delay has not elapsed yet")]
+    FeeIncreaseTimelocked,
+    #[msg("No fee increase is pending")]
+    NoPendingFees,
 }
@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct MintCreated {
+    pub mint: Pubkey,
+    pub authority: Pubkey,
+    pub transfer_hook_program: Pubkey,
+    pub interest_rate: i16,
+    pub decimals: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct VaultInitialized {
+    pub vault: Pubkey,
+    pub vault_authority: Pubkey,
+    pub mint: Pubkey,
+    pub vault_index: u64,
+    pub share_mint: Pubkey,
+    pub token_reserve: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DepositEvent {
+    pub vault: Pubkey,
+    /// Owner of the position the deposit went into
+    pub depositor: Pubkey,
+    /// Owner of the tokens deposited, differs from `depositor` for `deposit_for`
+    pub payer: Pubkey,
+    /// Tokens taken from the payer, fee included
+    pub amount: u64,
+    pub fee: u64,
+    pub shares_minted: u64,
+    pub user_shares_after: u64,
+    pub user_balance_after: u64,
+    pub vault_balance_after: u64,
+    pub locked_until: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct WithdrawEvent {
+    pub vault: Pubkey,
+    pub withdrawer: Pubkey,
+    /// Tokens taken out of the position, fee included
+    pub amount: u64,
+    pub fee: u64,
+    pub shares_burned: u64,
+    pub user_shares_after: u64,
+    pub user_balance_after: u64,
+    pub vault_balance_after: u64,
+    pub timestamp: i64,
+}
@@ -0,0 +1,33 @@
+use anchor_lang::prelude::*;
+
+use crate::state::*;
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    pub new_authority: Signer<'info>,
+
+    /// The vault account - only the proposed authority can accept, `new_authority` signs
+    /// for it (or as one of its signers if it is a multisig)
+    #[account(
+        mut,
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+        constraint = vault.pending_authority != Pubkey::default() @ crate::errors::VaultError::NotPendingAuthority
+    )]
+    pub vault: Account<'info, Vault>,
+}
+
+impl<'info> AcceptAuthority<'info> {
+    pub fn accept_authority(&mut self) -> Result<()> {
+        self.vault.vault_authority = self.vault.pending_authority;
+        self.vault.pending_authority = Pubkey::default();
+
+        msg!("Vault authority transferred to {}", self.vault.vault_authority);
+        Ok(())
+    }
+}
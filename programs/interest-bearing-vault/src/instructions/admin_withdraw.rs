@@ -0,0 +1,139 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        Mint, TokenAccount, TokenInterface,
+    }
+};
+
+use crate::state::*;
+use crate::utils::*;
+
+#[derive(Accounts)]
+pub struct AdminWithdraw<'info> {
+    #[account(mut)]
+    pub vault_authority: Signer<'info>,
+
+    /// The vault account - only the vault authority can use the admin withdraw
+    #[account(
+        mut,
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// The mint associated with the vault
+    #[account(address = vault.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// The vault authority's token account, owned by the multisig itself when one governs the
+    /// vault, so withdrawn tokens never land in the wallet of whoever submitted the instruction
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault.vault_authority,
+        associated_token::token_program = token_program,
+    )]
+    pub authority_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// The vault's token reserve account
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: ExtraAccountMetaList account for transfer hook, checked against the mint's hook
+    pub extra_account_meta_list: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Transfer hook program, only needed for mints with a transfer hook
+    pub transfer_hook_program: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Whitelist account for the vault, the owner of the reserve (resolved via transfer hook)
+    pub vault_whitelist: Option<UncheckedAccount<'info>>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> AdminWithdraw<'info> {
+    /// Moves tokens out of the reserve to the vault authority.
+    /// The withdrawn tokens stop backing the vault shares, so every holder's claim shrinks pro rata.
+    pub fn admin_withdraw(&mut self, amount: u64) -> Result<()> {
+        require!(amount > 0, crate::errors::VaultError::InvalidAmount);
+        require!(!self.vault.emergency_shutdown, crate::errors::VaultError::VaultShutdown);
+
+        checkpoint_interest(&mut self.vault, &self.mint.to_account_info())?;
+        self.vault.absorb_reserve_surplus(self.vault_token_reserve.amount)?;
+        require!(
+            self.vault.token_reserve_amount >= amount,
+            crate::errors::VaultError::InsufficientFunds
+        );
+        require!(
+            self.vault.available_liquidity(self.vault_token_reserve.amount) >= amount,
+            crate::errors::VaultError::InsufficientLiquidity
+        );
+
+        transfer_from_reserve(
+            &self.token_program,
+            &self.vault,
+            &self.vault_token_reserve,
+            &self.mint,
+            self.authority_token_account.to_account_info(),
+            &hook_accounts(
+                &self.mint,
+                self.extra_account_meta_list.as_ref(),
+                self.vault_whitelist.as_ref(),
+                self.transfer_hook_program.as_ref(),
+            )?,
+            amount,
+        )?;
+
+        self.vault.token_reserve_amount = self.vault.token_reserve_amount
+            .checked_sub(amount)
+            .ok_or(crate::errors::VaultError::Underflow)?;
+
+        msg!("Admin withdrew {} tokens from vault", amount);
+        msg!("Remaining vault balance: {}", self.vault.token_reserve_amount);
+
+        Ok(())
+    }
+
+    /// Moves the surplus set aside under `SurplusPolicy::Recoverable` to the vault authority.
+    /// Share holders have no claim on it, so their balances are unaffected.
+    pub fn recover_excess(&mut self) -> Result<()> {
+        require!(!self.vault.emergency_shutdown, crate::errors::VaultError::VaultShutdown);
+        checkpoint_interest(&mut self.vault, &self.mint.to_account_info())?;
+        self.vault.absorb_reserve_surplus(self.vault_token_reserve.amount)?;
+        let amount = self.vault.excess_reserve;
+        require!(amount > 0, crate::errors::VaultError::NoExcessReserve);
+
+        transfer_from_reserve(
+            &self.token_program,
+            &self.vault,
+            &self.vault_token_reserve,
+            &self.mint,
+            self.authority_token_account.to_account_info(),
+            &hook_accounts(
+                &self.mint,
+                self.extra_account_meta_list.as_ref(),
+                self.vault_whitelist.as_ref(),
+                self.transfer_hook_program.as_ref(),
+            )?,
+            amount,
+        )?;
+
+        self.vault.excess_reserve = 0;
+
+        msg!("Recovered {} excess tokens from vault", amount);
+        Ok(())
+    }
+}
@@ -0,0 +1,398 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::{create_idempotent, get_associated_token_address_with_program_id, AssociatedToken, Create},
+    token_2022::Token2022,
+    token_interface::{
+        Mint, TokenAccount, TokenInterface,
+        mint_to, MintTo,
+    }
+};
+use spl_token_2022::onchain;
+
+use crate::events::DepositEvent;
+use crate::state::*;
+use crate::utils::*;
+
+/// Accounts per beneficiary in `batch_deposit`'s remaining accounts: the beneficiary,
+/// its registry entry, its share token account and its whitelist entry, which is
+/// ignored for mints without a transfer hook.
+pub const BATCH_DEPOSIT_ACCOUNTS: usize = 4;
+
+/// Accounts per recipient in `batch_payout`'s remaining accounts: the recipient token
+/// account and its owner's whitelist entry, which is ignored for mints without a
+/// transfer hook.
+pub const BATCH_PAYOUT_ACCOUNTS: usize = 2;
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct BatchDeposit<'info> {
+    /// Funds every deposit in the batch with a single transfer
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The vault account
+    #[account(
+        mut,
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+        constraint = !vault.deposits_paused @ crate::errors::VaultError::VaultPaused,
+        constraint = !vault.emergency_shutdown @ crate::errors::VaultError::VaultShutdown
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// The mint associated with the vault
+    #[account(
+        mut,
+        address = vault.mint,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// The payer's token account
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program,
+    )]
+    pub payer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// The vault's token reserve account
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    /// The vault's share mint
+    #[account(
+        mut,
+        seeds = [b"share_mint", vault.key().as_ref()],
+        bump = vault.share_mint_bump,
+    )]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: ExtraAccountMetaList account for transfer hook, checked against the mint's hook
+    pub extra_account_meta_list: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Transfer hook program, only needed for mints with a transfer hook
+    pub transfer_hook_program: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Whitelist account for the payer, the owner of the source account (resolved via transfer hook)
+    pub payer_whitelist: Option<UncheckedAccount<'info>>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub share_token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> BatchDeposit<'info> {
+    /// Credits `amounts[i]` to the i-th beneficiary in `remaining_accounts`, funded by one
+    /// transfer of the total from the payer. Each credit is priced and capped like a
+    /// `deposit_for`, registry entries and share accounts are created as needed.
+    pub fn batch_deposit(
+        &mut self,
+        amounts: &[u64],
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<Vec<DepositEvent>> {
+        require!(
+            !amounts.is_empty() && remaining_accounts.len() == amounts.len() * BATCH_DEPOSIT_ACCOUNTS,
+            crate::errors::VaultError::InvalidBatch
+        );
+        let total = amounts
+            .iter()
+            .try_fold(0u64, |total, amount| total.checked_add(*amount))
+            .ok_or(crate::errors::VaultError::Overflow)?;
+        // A single transfer funds every beneficiary, its fee cannot be split between them exactly
+        require!(
+            transfer_fee(&self.mint.to_account_info(), total)?.unwrap_or(0) == 0,
+            crate::errors::VaultError::TransferFeeNotSupported
+        );
+
+        checkpoint_interest(&mut self.vault, &self.mint.to_account_info())?;
+        self.vault.absorb_reserve_surplus(self.vault_token_reserve.amount)?;
+
+        onchain::invoke_transfer_checked(
+            &self.token_program.key(),
+            self.payer_token_account.to_account_info(),
+            self.mint.to_account_info(),
+            self.vault_token_reserve.to_account_info(),
+            self.payer.to_account_info(),
+            &hook_accounts(
+                &self.mint,
+                self.extra_account_meta_list.as_ref(),
+                self.payer_whitelist.as_ref(),
+                self.transfer_hook_program.as_ref(),
+            )?,
+            total,
+            self.mint.decimals,
+            &[],
+        )?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let mut share_supply = self.share_mint.supply;
+        let mut events = Vec::with_capacity(amounts.len());
+        for (amount, accounts) in amounts.iter().zip(remaining_accounts.chunks(BATCH_DEPOSIT_ACCOUNTS)) {
+            let event = self.credit(*amount, accounts, share_supply, now)?;
+            share_supply = share_supply
+                .checked_add(event.shares_minted)
+                .ok_or(crate::errors::VaultError::Overflow)?;
+            events.push(event);
+        }
+
+        msg!("Deposited {} tokens for {} beneficiaries", total, amounts.len());
+        Ok(events)
+    }
+
+    /// Credits `amount` to the beneficiary in `accounts`, `share_supply` is the share
+    /// supply before this credit.
+    fn credit(
+        &mut self,
+        amount: u64,
+        accounts: &[AccountInfo<'info>],
+        share_supply: u64,
+        now: i64,
+    ) -> Result<DepositEvent> {
+        let [beneficiary, registry_info, share_account, beneficiary_whitelist] = accounts else {
+            return err!(crate::errors::VaultError::InvalidBatch);
+        };
+        require!(amount > 0, crate::errors::VaultError::InvalidAmount);
+        if let Some(hook_program_id) = transfer_hook_program_id(&self.mint.to_account_info())? {
+            require!(
+                is_whitelisted(beneficiary_whitelist, &hook_program_id, &self.mint.key(), beneficiary.key),
+                crate::errors::VaultError::BeneficiaryNotWhitelisted
+            );
+        }
+
+        let vault_key = self.vault.key();
+        let (registry_key, registry_bump) = Pubkey::find_program_address(
+            &[b"vault_registry", vault_key.as_ref(), beneficiary.key.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(registry_info.key(), registry_key, crate::errors::VaultError::InvalidBatch);
+        require!(registry_info.is_writable, crate::errors::VaultError::InvalidBatch);
+
+        let mut entry = if registry_info.owner == &crate::ID {
+            VaultRegistryEntry::try_deserialize(&mut &registry_info.try_borrow_data()?[..])?
+        } else {
+            create_pda_account(
+                &self.system_program.to_account_info(),
+                &self.payer.to_account_info(),
+                registry_info,
+                8 + VaultRegistryEntry::INIT_SPACE,
+                &crate::ID,
+                &[&[
+                    b"vault_registry",
+                    vault_key.as_ref(),
+                    beneficiary.key.as_ref(),
+                    &[registry_bump],
+                ]],
+            )?;
+
+            VaultRegistryEntry {
+                user: beneficiary.key(),
+                user_ata: get_associated_token_address_with_program_id(
+                    beneficiary.key,
+                    &self.mint.key(),
+                    &self.token_program.key(),
+                ),
+                vault: vault_key,
+                mint: self.mint.key(),
+                token_balance: 0,
+                shares: 0,
+                interest_inclusive_balance: 0,
+                accrued_interest: 0,
+                last_accrual_timestamp: 0,
+                locked_until: 0,
+                num_withdraws: 0,
+                num_deposits: 0,
+                bump: registry_bump,
+            }
+        };
+
+        let (fee, shares) = self.vault.credit_deposit(
+            &mut entry,
+            amount,
+            share_supply,
+            &self.mint.to_account_info(),
+            now,
+        )?;
+
+        create_idempotent(CpiContext::new(
+            self.associated_token_program.to_account_info(),
+            Create {
+                payer: self.payer.to_account_info(),
+                associated_token: share_account.clone(),
+                authority: beneficiary.clone(),
+                mint: self.share_mint.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+                token_program: self.share_token_program.to_account_info(),
+            },
+        ))?;
+
+        let vault_creator = self.vault.creator;
+        let vault_mint = self.vault.mint;
+        let vault_index = self.vault.vault_index.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault".as_ref(),
+            vault_creator.as_ref(),
+            vault_mint.as_ref(),
+            vault_index.as_ref(),
+            &[self.vault.bump],
+        ]];
+
+        mint_to(
+            CpiContext::new_with_signer(
+                self.share_token_program.to_account_info(),
+                MintTo {
+                    mint: self.share_mint.to_account_info(),
+                    to: share_account.clone(),
+                    authority: self.vault.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            shares,
+        )?;
+
+        entry.try_serialize(&mut &mut registry_info.try_borrow_mut_data()?[..])?;
+
+        Ok(DepositEvent {
+            vault: vault_key,
+            depositor: beneficiary.key(),
+            payer: self.payer.key(),
+            amount,
+            fee,
+            shares_minted: shares,
+            user_shares_after: entry.shares,
+            user_balance_after: entry.token_balance,
+            vault_balance_after: self.vault.token_reserve_amount,
+            locked_until: entry.locked_until,
+            timestamp: now,
+        })
+    }
+}
+
+#[derive(Accounts)]
+pub struct BatchPayout<'info> {
+    pub vault_authority: Signer<'info>,
+
+    /// The vault account - only the vault authority can pay out of the reserve
+    #[account(
+        mut,
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+        constraint = !vault.emergency_shutdown @ crate::errors::VaultError::VaultShutdown
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// The mint associated with the vault
+    #[account(address = vault.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// The vault's token reserve account
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: ExtraAccountMetaList account for transfer hook, checked against the mint's hook
+    pub extra_account_meta_list: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Transfer hook program, only needed for mints with a transfer hook
+    pub transfer_hook_program: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Whitelist account for the vault, the owner of the reserve (resolved via transfer hook)
+    pub vault_whitelist: Option<UncheckedAccount<'info>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> BatchPayout<'info> {
+    /// Pays `amounts[i]` out of the reserve to the i-th recipient in `recipients`, which lead
+    /// the remaining accounts ahead of any multisig approval accounts.
+    /// Like `admin_withdraw`, every share holder's claim shrinks pro rata. Any transfer fee
+    /// comes out of what each recipient gets and must not exceed `max_fee` per payout.
+    pub fn batch_payout(
+        &mut self,
+        amounts: &[u64],
+        max_fee: u64,
+        recipients: &'info [AccountInfo<'info>],
+    ) -> Result<()> {
+        require!(
+            !amounts.is_empty() && recipients.len() == amounts.len() * BATCH_PAYOUT_ACCOUNTS,
+            crate::errors::VaultError::InvalidBatch
+        );
+        let total = amounts
+            .iter()
+            .try_fold(0u64, |total, amount| total.checked_add(*amount))
+            .ok_or(crate::errors::VaultError::Overflow)?;
+
+        checkpoint_interest(&mut self.vault, &self.mint.to_account_info())?;
+        self.vault.absorb_reserve_surplus(self.vault_token_reserve.amount)?;
+        require!(
+            self.vault.token_reserve_amount >= total,
+            crate::errors::VaultError::InsufficientFunds
+        );
+        require!(
+            self.vault.available_liquidity(self.vault_token_reserve.amount) >= total,
+            crate::errors::VaultError::InsufficientLiquidity
+        );
+
+        let hook_program_id = transfer_hook_program_id(&self.mint.to_account_info())?;
+        for (amount, accounts) in amounts.iter().zip(recipients.chunks(BATCH_PAYOUT_ACCOUNTS)) {
+            let [recipient, recipient_whitelist] = accounts else {
+                return err!(crate::errors::VaultError::InvalidBatch);
+            };
+            require!(*amount > 0, crate::errors::VaultError::InvalidAmount);
+
+            let recipient_account = InterfaceAccount::<TokenAccount>::try_from(recipient)?;
+            require_keys_eq!(recipient_account.mint, self.mint.key(), crate::errors::VaultError::InvalidBatch);
+            if let Some(hook_program_id) = hook_program_id {
+                require!(
+                    is_whitelisted(recipient_whitelist, &hook_program_id, &self.mint.key(), &recipient_account.owner),
+                    crate::errors::VaultError::RecipientNotWhitelisted
+                );
+            }
+
+            transfer_from_reserve_with_max_fee(
+                &self.token_program,
+                &self.vault,
+                &self.vault_token_reserve,
+                &self.mint,
+                recipient.clone(),
+                &hook_accounts(
+                    &self.mint,
+                    self.extra_account_meta_list.as_ref(),
+                    self.vault_whitelist.as_ref(),
+                    self.transfer_hook_program.as_ref(),
+                )?,
+                *amount,
+                max_fee,
+            )?;
+        }
+
+        self.vault.token_reserve_amount = self.vault.token_reserve_amount
+            .checked_sub(total)
+            .ok_or(crate::errors::VaultError::Underflow)?;
+
+        msg!("Paid out {} tokens to {} recipients", total, amounts.len());
+        msg!("Remaining vault balance: {}", self.vault.token_reserve_amount);
+
+        Ok(())
+    }
+}
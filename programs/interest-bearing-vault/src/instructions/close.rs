@@ -0,0 +1,205 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        Mint, TokenAccount, TokenInterface,
+        close_account, CloseAccount,
+        harvest_withheld_tokens_to_mint, HarvestWithheldTokensToMint,
+    }
+};
+
+use crate::state::*;
+use crate::utils::*;
+
+#[derive(Accounts)]
+pub struct ClosePosition<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// CHECK: The position's vault, may already have been closed
+    #[account(mut, address = vault_registry_entry.vault)]
+    pub vault: UncheckedAccount<'info>,
+
+    /// The user's position, its rent goes back to the user
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"vault_registry", vault_registry_entry.vault.as_ref(), user.key().as_ref()],
+        bump = vault_registry_entry.bump,
+        constraint = vault_registry_entry.user == user.key() @ crate::errors::VaultError::Unauthorized,
+        constraint = vault_registry_entry.shares == 0 @ crate::errors::VaultError::PositionNotEmpty
+    )]
+    pub vault_registry_entry: Account<'info, VaultRegistryEntry>,
+}
+
+impl<'info> ClosePosition<'info> {
+    /// Closes an empty position, also once its vault has been closed.
+    pub fn close_position(&mut self) -> Result<()> {
+        if self.vault.owner == &crate::ID {
+            let mut vault = Vault::try_deserialize(&mut &self.vault.try_borrow_data()?[..])?;
+            vault.open_positions = vault.open_positions
+                .checked_sub(1)
+                .ok_or(crate::errors::VaultError::Underflow)?;
+            vault.try_serialize(&mut &mut self.vault.try_borrow_mut_data()?[..])?;
+        }
+
+        msg!("Closed position of {}", self.user.key());
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct CloseVault<'info> {
+    #[account(mut)]
+    pub vault_authority: Signer<'info>,
+
+    /// The vault account - only the vault authority can close it, its rent goes back to them
+    #[account(
+        mut,
+        close = vault_authority,
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+        constraint = vault.queued_withdrawals == 0
+            && vault.accrued_fees == 0
+            @ crate::errors::VaultError::VaultNotEmpty
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// Role assignments of the vault, needed when the signer acts through a role, closed with the vault
+    #[account(
+        mut,
+        close = vault_authority,
+        seeds = [b"vault_roles", vault.key().as_ref()],
+        bump = vault_roles.bump,
+    )]
+    pub vault_roles: Option<Account<'info, VaultRoles>>,
+
+    /// The vault's rate history, closed with the vault
+    #[account(
+        mut,
+        close = vault_authority,
+        seeds = [b"rate_history", vault.key().as_ref()],
+        bump = rate_history.bump,
+    )]
+    pub rate_history: Option<Account<'info, RateHistory>>,
+
+    /// A rate change still pending, closed with the vault
+    #[account(
+        mut,
+        close = vault_authority,
+        seeds = [b"pending_rate", vault.key().as_ref()],
+        bump = pending_rate_change.bump,
+    )]
+    pub pending_rate_change: Option<Account<'info, PendingRateChange>>,
+
+    /// The mint associated with the vault, receives the transfer fees withheld in the reserve
+    #[account(mut, address = vault.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// The vault's share mint
+    #[account(
+        address = vault.share_mint,
+        constraint = share_mint.supply == 0 @ crate::errors::VaultError::VaultNotEmpty
+    )]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    /// The vault authority's token account (the multisig's when one governs the vault),
+    /// receives whatever dust is left in the reserve
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault.vault_authority,
+        associated_token::token_program = token_program,
+    )]
+    pub authority_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// The vault's token reserve account
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: ExtraAccountMetaList account for transfer hook, checked against the mint's hook
+    pub extra_account_meta_list: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Transfer hook program, only needed for mints with a transfer hook
+    pub transfer_hook_program: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Whitelist account for the vault, the owner of the reserve (resolved via transfer hook)
+    pub vault_whitelist: Option<UncheckedAccount<'info>>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CloseVault<'info> {
+    /// Sweeps rounding dust out of the reserve and harvests the transfer fees withheld in it
+    /// to the mint, where the mint's withdraw authority can still claim them. Then closes the
+    /// reserve, the vault and the vault's role, rate history and pending rate accounts passed in. Empty positions stay
+    /// with their owners, who can still close them. The share mint cannot be closed, so
+    /// the vault's index cannot be used for a new vault.
+    pub fn close_vault(&mut self) -> Result<()> {
+        let dust = self.vault_token_reserve.amount;
+        if dust > 0 {
+            transfer_from_reserve(
+                &self.token_program,
+                &self.vault,
+                &self.vault_token_reserve,
+                &self.mint,
+                self.authority_token_account.to_account_info(),
+                &hook_accounts(
+                    &self.mint,
+                    self.extra_account_meta_list.as_ref(),
+                    self.vault_whitelist.as_ref(),
+                    self.transfer_hook_program.as_ref(),
+                )?,
+                dust,
+            )?;
+        }
+
+        // Token-2022 refuses to close an account still holding withheld fees
+        if withheld_transfer_fees(&self.vault_token_reserve.to_account_info())? > 0 {
+            harvest_withheld_tokens_to_mint(
+                CpiContext::new(
+                    self.token_program.to_account_info(),
+                    HarvestWithheldTokensToMint {
+                        token_program_id: self.token_program.to_account_info(),
+                        mint: self.mint.to_account_info(),
+                    },
+                ),
+                vec![self.vault_token_reserve.to_account_info()],
+            )?;
+        }
+
+        let vault_index = self.vault.vault_index.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault".as_ref(),
+            self.vault.creator.as_ref(),
+            self.vault.mint.as_ref(),
+            vault_index.as_ref(),
+            &[self.vault.bump],
+        ]];
+
+        close_account(CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            CloseAccount {
+                account: self.vault_token_reserve.to_account_info(),
+                destination: self.vault_authority.to_account_info(),
+                authority: self.vault.to_account_info(),
+            },
+            signer_seeds,
+        ))?;
+
+        msg!("Closed vault {}", self.vault.key());
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::state::*;
+use crate::utils::*;
+
+#[derive(Accounts)]
+pub struct CollectFees<'info> {
+    pub vault_authority: Signer<'info>,
+
+    /// The vault account - only the vault authority can collect fees
+    #[account(
+        mut,
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+        constraint = vault.fee_recipient != Pubkey::default() @ crate::errors::VaultError::FeeRecipientNotSet
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// Role assignments of the vault, needed when the signer acts through a role
+    #[account(
+        seeds = [b"vault_roles", vault.key().as_ref()],
+        bump = vault_roles.bump,
+    )]
+    pub vault_roles: Option<Account<'info, VaultRoles>>,
+
+    /// The mint associated with the vault
+    #[account(address = vault.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// The fee recipient's token account recorded on the vault
+    #[account(
+        mut,
+        address = vault.fee_recipient,
+        token::mint = mint,
+        token::token_program = token_program,
+    )]
+    pub fee_recipient: InterfaceAccount<'info, TokenAccount>,
+
+    /// The vault's token reserve account
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: ExtraAccountMetaList account for transfer hook, checked against the mint's hook
+    pub extra_account_meta_list: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Transfer hook program, only needed for mints with a transfer hook
+    pub transfer_hook_program: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Whitelist account for the vault, the owner of the reserve (resolved via transfer hook)
+    pub vault_whitelist: Option<UncheckedAccount<'info>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> CollectFees<'info> {
+    /// Pays all accrued fees out of the reserve to the fee recipient. After an emergency
+    /// shutdown the fees take the same haircut as the depositors' exits.
+    pub fn collect_fees(&mut self) -> Result<()> {
+        let fees = self.vault.accrued_fees;
+        require!(fees > 0, crate::errors::VaultError::InvalidAmount);
+        let payout = if self.vault.emergency_shutdown {
+            self.vault.emergency_payout(fees, self.vault_token_reserve.amount)?
+        } else {
+            fees
+        };
+
+        transfer_from_reserve(
+            &self.token_program,
+            &self.vault,
+            &self.vault_token_reserve,
+            &self.mint,
+            self.fee_recipient.to_account_info(),
+            &hook_accounts(
+                &self.mint,
+                self.extra_account_meta_list.as_ref(),
+                self.vault_whitelist.as_ref(),
+                self.transfer_hook_program.as_ref(),
+            )?,
+            payout,
+        )?;
+
+        self.vault.accrued_fees = 0;
+
+        msg!("Collected {} of {} tokens of fees", payout, fees);
+        Ok(())
+    }
+}
@@ -1,17 +1,17 @@
-use anchor_lang::{prelude::*, solana_program::program::{invoke}};
-use anchor_lang::solana_program::program::invoke_signed;
+use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
+    token_2022::Token2022,
     token_interface::{
         Mint, TokenAccount, TokenInterface,
-        transfer_checked, TransferChecked,
+        mint_to, MintTo,
     }
 };
-use spl_tlv_account_resolution::state::ExtraAccountMetaList;
-use spl_token_2022::{onchain, instruction as spl_2022_instruction};
-use spl_transfer_hook_interface::instruction::ExecuteInstruction;
+use crate::events::DepositEvent;
 use crate::state::*;
+use crate::utils::*;
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct Deposit<'info> {
     #[account(mut)]
@@ -20,8 +20,15 @@ pub struct Deposit<'info> {
     /// The vault account
     #[account(
         mut,
-        seeds = [b"vault", vault.vault_authority.as_ref()],
-        bump = vault.bump
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+        constraint = !vault.deposits_paused @ crate::errors::VaultError::VaultPaused,
+        constraint = !vault.emergency_shutdown @ crate::errors::VaultError::VaultShutdown
     )]
     pub vault: Account<'info, Vault>,
 
@@ -37,7 +44,7 @@ pub struct Deposit<'info> {
     /// The mint associated with the vault
     #[account(
         mut,
-        extensions::transfer_hook::program_id = transfer_hook_program.key(),
+        address = vault.mint,
     )]
     pub mint: InterfaceAccount<'info, Mint>,
 
@@ -59,96 +66,136 @@ pub struct Deposit<'info> {
     )]
     pub vault_token_reserve: InterfaceAccount<'info, TokenAccount>,
 
-    /// CHECK: ExtraAccountMetaList account for transfer hook
+    /// The vault's share mint
     #[account(
-        seeds = [b"extra-account-metas", mint.key().as_ref()],
-        bump,
-        seeds::program = transfer_hook_program.key()
+        mut,
+        seeds = [b"share_mint", vault.key().as_ref()],
+        bump = vault.share_mint_bump,
     )]
-    pub extra_account_meta_list: AccountInfo<'info>,
+    pub share_mint: InterfaceAccount<'info, Mint>,
 
-    /// CHECK: Transfer hook program
-    pub transfer_hook_program: UncheckedAccount<'info>,
-
-    /// CHECK: Whitelist account for depositor (resolved via transfer hook)
+    /// The depositor's share token account
     #[account(
-        mut,
-        seeds = [b"whitelist", mint.key().as_ref(), depositor.key().as_ref()],
-        bump,
-        seeds::program = transfer_hook_program.key()
+        init_if_needed,
+        payer = depositor,
+        associated_token::mint = share_mint,
+        associated_token::authority = depositor,
+        associated_token::token_program = share_token_program,
     )]
-    pub depositor_whitelist_PDA: UncheckedAccount<'info>,
+    pub depositor_share_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: ExtraAccountMetaList account for transfer hook, checked against the mint's hook
+    pub extra_account_meta_list: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Transfer hook program, only needed for mints with a transfer hook
+    pub transfer_hook_program: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Whitelist account for depositor (resolved via transfer hook)
+    pub depositor_whitelist_PDA: Option<UncheckedAccount<'info>>,
 
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Interface<'info, TokenInterface>,
+    pub share_token_program: Program<'info, Token2022>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> Deposit<'info> {
-    pub fn deposit(&mut self, amount: u64, registry_bump:u8) -> Result<()> {
+    /// Deposits `amount` tokens, locking the whole position for at least `lock_duration`
+    /// seconds, at most `MAX_LOCK_DURATION`. A new lock never shortens an existing one. For mints
+    /// charging a transfer fee, `max_fee` bounds the fee and only the tokens the reserve receives
+    /// are credited.
+    pub fn deposit(&mut self, amount: u64, lock_duration: i64, max_fee: u64, registry_bump:u8) -> Result<DepositEvent> {
         require!(amount > 0, crate::errors::VaultError::InvalidAmount);
+        require!(
+            (0..=MAX_LOCK_DURATION).contains(&lock_duration),
+            crate::errors::VaultError::InvalidLockDuration
+        );
         
         msg!("Deposit has been initiated");
-       
-        onchain::invoke_transfer_checked(
-            &self.token_program.key(),
+
+        // Transfer-fee mints withhold part of `amount`, only what the reserve receives is credited
+        let reserve_before = self.vault_token_reserve.amount;
+        transfer_checked_with_max_fee(
+            &self.token_program,
             self.depositor_token_account.to_account_info(),
-            self.mint.to_account_info(),
+            &self.mint,
             self.vault_token_reserve.to_account_info(),
             self.depositor.to_account_info(),
-            &[
-                self.extra_account_meta_list.to_account_info(),
-                self.depositor_whitelist_PDA.to_account_info(),
-                self.transfer_hook_program.to_account_info(),
-            ],
+            &hook_accounts(
+                &self.mint,
+                self.extra_account_meta_list.as_ref(),
+                self.depositor_whitelist_PDA.as_ref(),
+                self.transfer_hook_program.as_ref(),
+            )?,
             amount,
-            self.mint.decimals,
+            max_fee,
             &[], // No signer seeds needed - depositor is already a signer
         )?;
-       
-       // msg!("Deposited {} tokens to PDA vault", amount);
-        
-        // Update vault state
-        self.vault.token_reserve_amount = self.vault.token_reserve_amount
-            .checked_add(amount)
-            .ok_or(crate::errors::VaultError::Overflow)?;
-        
-        // Increment depositor count for deposit
-        self.vault.num_depositors = self.vault.num_depositors
-            .checked_add(1)
-            .ok_or(crate::errors::VaultError::Overflow)?;
-        
-        // msg!("Deposited {} tokens to vault reserve {:?}", amount, &self.vault_token_reserve.key());
-        // msg!("Total vault balance: {}", self.vault.token_reserve_amount);
-        // msg!("Total depositors: {}", self.vault.num_depositors);
-        
-        // Update vault registry
-        let v = VaultRegistryEntry{
-            user: self.depositor.key(),
-            user_ata: self.depositor_token_account.key(),
+        self.vault_token_reserve.reload()?;
+        let received = self.vault_token_reserve.amount
+            .checked_sub(reserve_before)
+            .ok_or(crate::errors::VaultError::Underflow)?;
+
+        // Price the deposit before the reserve grows
+        checkpoint_interest(&mut self.vault, &self.mint.to_account_info())?;
+        self.vault.absorb_reserve_surplus(reserve_before)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let entry = &mut self.vault_registry_entry;
+        entry.user = self.depositor.key();
+        entry.user_ata = self.depositor_token_account.key();
+        entry.vault = self.vault.key();
+        entry.mint = self.mint.key();
+        entry.bump = registry_bump;
+        entry.locked_until = entry.locked_until.max(
+            now.checked_add(lock_duration)
+                .ok_or(crate::errors::VaultError::Overflow)?
+        );
+        let (fee, shares) = self.vault.credit_deposit(
+            entry,
+            received,
+            self.share_mint.supply,
+            &self.mint.to_account_info(),
+            now,
+        )?;
+
+        let vault_creator = self.vault.creator;
+        let vault_mint = self.vault.mint;
+        let vault_index = self.vault.vault_index.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault".as_ref(),
+            vault_creator.as_ref(),
+            vault_mint.as_ref(),
+            vault_index.as_ref(),
+            &[self.vault.bump],
+        ]];
+
+        mint_to(
+            CpiContext::new_with_signer(
+                self.share_token_program.to_account_info(),
+                MintTo {
+                    mint: self.share_mint.to_account_info(),
+                    to: self.depositor_share_account.to_account_info(),
+                    authority: self.vault.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            shares,
+        )?;
+
+        Ok(DepositEvent {
             vault: self.vault.key(),
-            mint: self.mint.key(),
-            token_balance: self.vault_registry_entry.token_balance
-                .checked_add(amount)
-                .ok_or(crate::errors::VaultError::Overflow)?,
-            num_withdraws: self.vault_registry_entry.num_withdraws,
-            num_deposits: self.vault_registry_entry.num_deposits
-                .checked_add(1)
-                .ok_or(crate::errors::VaultError::Overflow)?,
-            bump: registry_bump
-        };
-        self.vault_registry_entry.set_inner(v);
-        // 
-        // self.vault_registry_entry.vault = self.vault.key();
-        // self.vault_registry_entry.mint = self.mint.key();
-        // self.vault_registry_entry.token_balance = self.vault_registry_entry.token_balance
-        //         .checked_add(amount)
-        //         .ok_or(crate::errors::VaultError::Overflow)?;
-        // self.vault_registry_entry.deposits = self.vault_registry_entry.num_deposits
-        //         .checked_add(1)
-        //         .ok_or(crate::errors::VaultError::Overflow)?;
-        
-        Ok(())
+            depositor: self.depositor.key(),
+            payer: self.depositor.key(),
+            amount,
+            fee,
+            shares_minted: shares,
+            user_shares_after: self.vault_registry_entry.shares,
+            user_balance_after: self.vault_registry_entry.token_balance,
+            vault_balance_after: self.vault.token_reserve_amount,
+            locked_until: self.vault_registry_entry.locked_until,
+            timestamp: now,
+        })
     }
 }
 
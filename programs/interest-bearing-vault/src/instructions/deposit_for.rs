@@ -0,0 +1,220 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::{get_associated_token_address_with_program_id, AssociatedToken},
+    token_2022::Token2022,
+    token_interface::{
+        Mint, TokenAccount, TokenInterface,
+        mint_to, MintTo,
+    }
+};
+
+use crate::events::DepositEvent;
+use crate::state::*;
+use crate::utils::*;
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct DepositFor<'info> {
+    /// Funds the deposit, its tokens leave its own token account
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Owner of the funded position, only needs to be whitelisted
+    pub beneficiary: UncheckedAccount<'info>,
+
+    /// The vault account
+    #[account(
+        mut,
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+        constraint = !vault.deposits_paused @ crate::errors::VaultError::VaultPaused,
+        constraint = !vault.emergency_shutdown @ crate::errors::VaultError::VaultShutdown
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// The beneficiary's position
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + VaultRegistryEntry::INIT_SPACE,
+        seeds = [b"vault_registry", vault.key().as_ref(), beneficiary.key().as_ref()],
+        bump,
+    )]
+    pub vault_registry_entry: Account<'info, VaultRegistryEntry>,
+
+    /// The mint associated with the vault
+    #[account(
+        mut,
+        address = vault.mint,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// The payer's token account
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program,
+    )]
+    pub payer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// The vault's token reserve account
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    /// The vault's share mint
+    #[account(
+        mut,
+        seeds = [b"share_mint", vault.key().as_ref()],
+        bump = vault.share_mint_bump,
+    )]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    /// The beneficiary's share token account
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = share_mint,
+        associated_token::authority = beneficiary,
+        associated_token::token_program = share_token_program,
+    )]
+    pub beneficiary_share_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: ExtraAccountMetaList account for transfer hook, checked against the mint's hook
+    pub extra_account_meta_list: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Transfer hook program, only needed for mints with a transfer hook
+    pub transfer_hook_program: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Whitelist account for the payer, the owner of the source account (resolved via transfer hook)
+    pub payer_whitelist: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Whitelist account for the beneficiary, only exists once the transfer hook whitelisted it,
+    /// checked in the handler for mints with a transfer hook
+    pub beneficiary_whitelist: Option<UncheckedAccount<'info>>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub share_token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> DepositFor<'info> {
+    /// Deposits `amount` of the payer's tokens into the beneficiary's position. Caps apply to
+    /// the beneficiary, and the position keeps its lock, only the beneficiary can extend it.
+    /// `max_fee` bounds the transfer fee the payer pays, as in [`Deposit::deposit`].
+    pub fn deposit_for(
+        &mut self,
+        amount: u64,
+        max_fee: u64,
+        registry_bump: u8,
+    ) -> Result<DepositEvent> {
+        require!(amount > 0, crate::errors::VaultError::InvalidAmount);
+        // Hooked mints only credit whitelisted beneficiaries
+        if let Some(hook_program_id) = transfer_hook_program_id(&self.mint.to_account_info())? {
+            require!(
+                self.beneficiary_whitelist.as_ref().is_some_and(|whitelist| {
+                    is_whitelisted(whitelist, &hook_program_id, &self.mint.key(), self.beneficiary.key)
+                }),
+                crate::errors::VaultError::BeneficiaryNotWhitelisted
+            );
+        }
+
+        // Transfer-fee mints withhold part of `amount`, only what the reserve receives is credited
+        let reserve_before = self.vault_token_reserve.amount;
+        transfer_checked_with_max_fee(
+            &self.token_program,
+            self.payer_token_account.to_account_info(),
+            &self.mint,
+            self.vault_token_reserve.to_account_info(),
+            self.payer.to_account_info(),
+            &hook_accounts(
+                &self.mint,
+                self.extra_account_meta_list.as_ref(),
+                self.payer_whitelist.as_ref(),
+                self.transfer_hook_program.as_ref(),
+            )?,
+            amount,
+            max_fee,
+            &[],
+        )?;
+        self.vault_token_reserve.reload()?;
+        let received = self.vault_token_reserve.amount
+            .checked_sub(reserve_before)
+            .ok_or(crate::errors::VaultError::Underflow)?;
+
+        // Price the deposit before the reserve grows
+        checkpoint_interest(&mut self.vault, &self.mint.to_account_info())?;
+        self.vault.absorb_reserve_surplus(reserve_before)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let beneficiary = self.beneficiary.key();
+        let entry = &mut self.vault_registry_entry;
+        entry.user = beneficiary;
+        entry.user_ata = get_associated_token_address_with_program_id(
+            &beneficiary,
+            &self.mint.key(),
+            &self.token_program.key(),
+        );
+        entry.vault = self.vault.key();
+        entry.mint = self.mint.key();
+        entry.bump = registry_bump;
+        let (fee, shares) = self.vault.credit_deposit(
+            entry,
+            received,
+            self.share_mint.supply,
+            &self.mint.to_account_info(),
+            now,
+        )?;
+
+        let vault_creator = self.vault.creator;
+        let vault_mint = self.vault.mint;
+        let vault_index = self.vault.vault_index.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault".as_ref(),
+            vault_creator.as_ref(),
+            vault_mint.as_ref(),
+            vault_index.as_ref(),
+            &[self.vault.bump],
+        ]];
+
+        mint_to(
+            CpiContext::new_with_signer(
+                self.share_token_program.to_account_info(),
+                MintTo {
+                    mint: self.share_mint.to_account_info(),
+                    to: self.beneficiary_share_account.to_account_info(),
+                    authority: self.vault.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            shares,
+        )?;
+
+        msg!("Deposited {} tokens for {}", amount, beneficiary);
+
+        Ok(DepositEvent {
+            vault: self.vault.key(),
+            depositor: beneficiary,
+            payer: self.payer.key(),
+            amount,
+            fee,
+            shares_minted: shares,
+            user_shares_after: self.vault_registry_entry.shares,
+            user_balance_after: self.vault_registry_entry.token_balance,
+            vault_balance_after: self.vault.token_reserve_amount,
+            locked_until: self.vault_registry_entry.locked_until,
+            timestamp: now,
+        })
+    }
+}
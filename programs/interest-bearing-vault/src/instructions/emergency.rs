@@ -0,0 +1,285 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_2022::Token2022,
+    token_interface::{
+        Mint, TokenAccount, TokenInterface,
+        burn, Burn,
+    }
+};
+
+use crate::events::WithdrawEvent;
+use crate::state::*;
+use crate::utils::*;
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct EmergencyWithdraw<'info> {
+    #[account(mut)]
+    pub withdrawer: Signer<'info>,
+
+    /// The vault account - only open for emergency withdrawals once it is shut down
+    #[account(
+        mut,
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+        constraint = vault.emergency_shutdown @ crate::errors::VaultError::VaultNotShutdown
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// The withdrawer's position - only its owner can withdraw from it
+    #[account(
+        mut,
+        seeds = [b"vault_registry", vault.key().as_ref(), withdrawer.key().as_ref()],
+        bump = vault_registry_entry.bump,
+        constraint = vault_registry_entry.user == withdrawer.key() @ crate::errors::VaultError::Unauthorized
+    )]
+    pub vault_registry_entry: Account<'info, VaultRegistryEntry>,
+
+    /// The mint associated with the vault
+    #[account(address = vault.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// The withdrawer's token account
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = withdrawer,
+        associated_token::token_program = token_program,
+    )]
+    pub withdrawer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// The vault's token reserve account
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    /// The vault's share mint
+    #[account(
+        mut,
+        seeds = [b"share_mint", vault.key().as_ref()],
+        bump = vault.share_mint_bump,
+    )]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    /// The withdrawer's share token account, shares are burned from here
+    #[account(
+        mut,
+        associated_token::mint = share_mint,
+        associated_token::authority = withdrawer,
+        associated_token::token_program = share_token_program,
+    )]
+    pub withdrawer_share_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: ExtraAccountMetaList account for transfer hook, checked against the mint's hook
+    pub extra_account_meta_list: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Transfer hook program, only needed for mints with a transfer hook
+    pub transfer_hook_program: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Whitelist account for the vault, the owner of the reserve (resolved via transfer hook)
+    pub vault_whitelist: Option<UncheckedAccount<'info>>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub share_token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> EmergencyWithdraw<'info> {
+    /// Burns all of the withdrawer's shares for their pro-rata part of what the reserve
+    /// still backs, which may be less than `token_balance`. Locks, pauses and the withdraw
+    /// fee do not apply. Any transfer fee, at most `max_fee`, comes out of the payout.
+    pub fn emergency_withdraw(&mut self, max_fee: u64) -> Result<WithdrawEvent> {
+        let shares = self.vault_registry_entry.shares;
+        require!(shares > 0, crate::errors::VaultError::InsufficientFunds);
+        checkpoint_interest(&mut self.vault, &self.mint.to_account_info())?;
+
+        // Pay out of what the reserve actually holds if it fell short of the books
+        let position_value = convert_to_assets(
+            shares,
+            self.vault.token_reserve_amount,
+            self.share_mint.supply,
+            Rounding::Down,
+        )?;
+        let amount = self.vault.emergency_payout(position_value, self.vault_token_reserve.amount)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let interest_inclusive_balance =
+            interest_inclusive_amount(&self.mint.to_account_info(), position_value, now)?;
+        self.vault_registry_entry.accrue_interest(interest_inclusive_balance, now)?;
+
+        burn(
+            CpiContext::new(
+                self.share_token_program.to_account_info(),
+                Burn {
+                    mint: self.share_mint.to_account_info(),
+                    from: self.withdrawer_share_account.to_account_info(),
+                    authority: self.withdrawer.to_account_info(),
+                },
+            ),
+            shares,
+        )?;
+
+        if amount > 0 {
+            transfer_from_reserve_with_max_fee(
+                &self.token_program,
+                &self.vault,
+                &self.vault_token_reserve,
+                &self.mint,
+                self.withdrawer_token_account.to_account_info(),
+                &hook_accounts(
+                    &self.mint,
+                    self.extra_account_meta_list.as_ref(),
+                    self.vault_whitelist.as_ref(),
+                    self.transfer_hook_program.as_ref(),
+                )?,
+                amount,
+                max_fee,
+            )?;
+        }
+
+        // The position leaves the books in full, so the haircut stays the same for everyone else
+        self.vault.token_reserve_amount = self.vault.token_reserve_amount
+            .checked_sub(position_value)
+            .ok_or(crate::errors::VaultError::Underflow)?;
+
+        msg!("Emergency withdrew {} tokens from vault", amount);
+        msg!("Remaining vault balance: {}", self.vault.token_reserve_amount);
+
+        self.vault_registry_entry.token_balance = 0;
+        self.vault_registry_entry.shares = 0;
+        self.vault_registry_entry.interest_inclusive_balance = 0;
+        self.vault_registry_entry.num_withdraws = self.vault_registry_entry.num_withdraws
+                .checked_add(1)
+                .ok_or(crate::errors::VaultError::Overflow)?;
+        self.vault.record_withdrawal(0)?;
+
+        Ok(WithdrawEvent {
+            vault: self.vault.key(),
+            withdrawer: self.withdrawer.key(),
+            amount,
+            fee: 0,
+            shares_burned: shares,
+            user_shares_after: 0,
+            user_balance_after: 0,
+            vault_balance_after: self.vault.token_reserve_amount,
+            timestamp: now,
+        })
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(request_id: u64)]
+pub struct EmergencyClaimWithdraw<'info> {
+    #[account(mut)]
+    pub withdrawer: Signer<'info>,
+
+    /// The vault account - only open for emergency claims once it is shut down
+    #[account(
+        mut,
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+        constraint = vault.emergency_shutdown @ crate::errors::VaultError::VaultNotShutdown
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// The claimed request, closed to the withdrawer once paid out
+    #[account(
+        mut,
+        close = withdrawer,
+        seeds = [b"withdrawal_request", vault.key().as_ref(), request_id.to_le_bytes().as_ref()],
+        bump = withdrawal_request.bump,
+        constraint = withdrawal_request.user == withdrawer.key() @ crate::errors::VaultError::WithdrawalRequestNotFound
+    )]
+    pub withdrawal_request: Account<'info, WithdrawalRequest>,
+
+    /// The mint associated with the vault
+    #[account(address = vault.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// The withdrawer's token account
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = withdrawer,
+        associated_token::token_program = token_program,
+    )]
+    pub withdrawer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// The vault's token reserve account
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: ExtraAccountMetaList account for transfer hook, checked against the mint's hook
+    pub extra_account_meta_list: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Transfer hook program, only needed for mints with a transfer hook
+    pub transfer_hook_program: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Whitelist account for the vault, the owner of the reserve (resolved via transfer hook)
+    pub vault_whitelist: Option<UncheckedAccount<'info>>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> EmergencyClaimWithdraw<'info> {
+    /// Pays out a withdrawal request queued before the shutdown, fulfilled or not, with the
+    /// same haircut `emergency_withdraw` applies to share holders. The cooldown does not apply,
+    /// `max_fee` bounds the transfer fee as in `emergency_withdraw`.
+    pub fn emergency_claim_withdraw(&mut self, request_id: u64, max_fee: u64) -> Result<()> {
+        let request = self.withdrawal_request.clone().into_inner();
+        let amount = self.vault.emergency_payout(request.amount, self.vault_token_reserve.amount)?;
+
+        if amount > 0 {
+            transfer_from_reserve_with_max_fee(
+                &self.token_program,
+                &self.vault,
+                &self.vault_token_reserve,
+                &self.mint,
+                self.withdrawer_token_account.to_account_info(),
+                &hook_accounts(
+                    &self.mint,
+                    self.extra_account_meta_list.as_ref(),
+                    self.vault_whitelist.as_ref(),
+                    self.transfer_hook_program.as_ref(),
+                )?,
+                amount,
+                max_fee,
+            )?;
+        }
+
+        self.vault.queued_withdrawals = self.vault.queued_withdrawals
+            .checked_sub(request.amount)
+            .ok_or(crate::errors::VaultError::Underflow)?;
+        if request.fulfilled {
+            self.vault.fulfilled_withdrawals = self.vault.fulfilled_withdrawals
+                .checked_sub(request.amount)
+                .ok_or(crate::errors::VaultError::Underflow)?;
+        }
+
+        msg!("Emergency claimed withdrawal request {} for {} tokens", request_id, amount);
+        Ok(())
+    }
+}
@@ -1,32 +1,53 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
+    token_2022::{
+        spl_token_2022::{extension::ExtensionType, state::Mint as Token2022Mint},
+        Token2022,
+    },
     token_interface::{
         Mint, TokenAccount, TokenInterface,
+        initialize_mint2, InitializeMint2,
+        non_transferable_mint_initialize, NonTransferableMintInitialize,
     }
 };
 
+use crate::events::VaultInitialized;
 use crate::state::*;
+use crate::utils::*;
 
+#[event_cpi]
 #[derive(Accounts)]
+#[instruction(vault_index: u64)]
 pub struct InitializeVault<'info> {
     #[account(mut)]
     pub vault_authority: Signer<'info>,
 
     pub mint: InterfaceAccount<'info, Mint>,
 
-    /// CHECK: Program id of the tf hook
-    pub hook_program_id: UncheckedAccount<'info>,
-
     #[account(
         init, 
         payer = vault_authority, 
         space = 8 + Vault::INIT_SPACE,
-        seeds = [b"vault", vault_authority.key().as_ref()],
+        seeds = [
+            b"vault",
+            vault_authority.key().as_ref(),
+            mint.key().as_ref(),
+            vault_index.to_le_bytes().as_ref(),
+        ],
         bump
     )]
     pub vault: Account<'info, Vault>,
 
+    /// CHECK: Share token minted to depositors, created as a non-transferable Token-2022 mint
+    /// in the handler, the vault is its mint authority
+    #[account(
+        mut,
+        seeds = [b"share_mint", vault.key().as_ref()],
+        bump,
+    )]
+    pub share_mint: UncheckedAccount<'info>,
+
     #[account(
         init,
         payer = vault_authority,
@@ -38,20 +59,105 @@ pub struct InitializeVault<'info> {
 
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of the share mint, always Token-2022
+    pub share_token_program: Program<'info, Token2022>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> InitializeVault<'info> {
-    pub fn initialize_vault(&mut self, bump: u8) -> Result<()> {
+    pub fn initialize_vault(&mut self, vault_index: u64, bump: u8, share_mint_bump: u8) -> Result<VaultInitialized> {
+        let now = Clock::get()?.unix_timestamp;
+        self.create_share_mint(share_mint_bump)?;
+
         let vault = &mut self.vault;
 
+        vault.creator = self.vault_authority.key();
         vault.vault_authority = self.vault_authority.key();
+        vault.pending_authority = Pubkey::default();
         vault.mint = self.mint.key();
+        vault.vault_index = vault_index;
         vault.token_reserve = self.token_reserve.key();
+        vault.share_mint = self.share_mint.key();
         vault.token_reserve_amount = 0;
         vault.num_depositors = 0;
+        vault.num_deposits = 0;
+        vault.num_withdrawals = 0;
+        vault.open_positions = 0;
+        vault.last_interest_realization = now;
+        vault.unrealized_interest = 0;
+        vault.min_rate = i16::MIN;
+        vault.max_rate = i16::MAX;
+        vault.rate_change_delay = DEFAULT_RATE_CHANGE_DELAY;
+        vault.pending_rate_change_delay = 0;
+        vault.pending_rate_change_delay_at = 0;
+        vault.deposits_paused = false;
+        vault.withdrawals_paused = false;
+        vault.emergency_shutdown = false;
+        vault.max_total_deposits = 0;
+        vault.max_deposit_per_user = 0;
+        vault.withdrawal_cooldown = 0;
+        vault.next_withdrawal_request_id = 0;
+        vault.next_withdrawal_to_fulfil = 0;
+        vault.queued_withdrawals = 0;
+        vault.fulfilled_withdrawals = 0;
+        vault.deposit_fee_bps = 0;
+        vault.withdraw_fee_bps = 0;
+        vault.performance_fee_bps = 0;
+        vault.pending_deposit_fee_bps = 0;
+        vault.pending_withdraw_fee_bps = 0;
+        vault.pending_performance_fee_bps = 0;
+        vault.pending_fees_at = 0;
+        vault.fee_recipient = Pubkey::default();
+        vault.accrued_fees = 0;
+        vault.surplus_policy = SurplusPolicy::Yield;
+        vault.excess_reserve = 0;
         vault.bump = bump;
+        vault.share_mint_bump = share_mint_bump;
 
-        Ok(())
+        Ok(VaultInitialized {
+            vault: self.vault.key(),
+            vault_authority: self.vault_authority.key(),
+            mint: self.mint.key(),
+            vault_index,
+            share_mint: self.share_mint.key(),
+            token_reserve: self.token_reserve.key(),
+            timestamp: now,
+        })
     }
-}
\ No newline at end of file
+
+    /// Creates the share mint with the NonTransferable extension. Positions are tracked by
+    /// the registry, so shares cannot move between wallets and only their holder redeems them.
+    fn create_share_mint(&self, share_mint_bump: u8) -> Result<()> {
+        let space = ExtensionType::try_calculate_account_len::<Token2022Mint>(&[ExtensionType::NonTransferable])?;
+        let vault_key = self.vault.key();
+
+        create_pda_account(
+            &self.system_program.to_account_info(),
+            &self.vault_authority.to_account_info(),
+            &self.share_mint.to_account_info(),
+            space,
+            &self.share_token_program.key(),
+            &[&[b"share_mint", vault_key.as_ref(), &[share_mint_bump]]],
+        )?;
+
+        non_transferable_mint_initialize(CpiContext::new(
+            self.share_token_program.to_account_info(),
+            NonTransferableMintInitialize {
+                token_program_id: self.share_token_program.to_account_info(),
+                mint: self.share_mint.to_account_info(),
+            },
+        ))?;
+
+        initialize_mint2(
+            CpiContext::new(
+                self.share_token_program.to_account_info(),
+                InitializeMint2 {
+                    mint: self.share_mint.to_account_info(),
+                },
+            ),
+            self.mint.decimals,
+            &vault_key,
+            None,
+        )
+    }
+}
@@ -0,0 +1,235 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_spl::token_interface::{Mint, TokenInterface};
+use anchor_spl::token_2022::spl_token_2022::extension::interest_bearing_mint::instruction::update_rate;
+
+use crate::state::*;
+use crate::utils::*;
+
+#[derive(Accounts)]
+pub struct ProposeRateChange<'info> {
+    #[account(mut)]
+    pub vault_authority: Signer<'info>,
+
+    /// The vault account - only the vault authority can propose rate changes
+    #[account(
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// Role assignments of the vault, needed when the signer acts through a role
+    #[account(
+        seeds = [b"vault_roles", vault.key().as_ref()],
+        bump = vault_roles.bump,
+    )]
+    pub vault_roles: Option<Account<'info, VaultRoles>>,
+
+    /// The proposed change, a vault has at most one pending change
+    #[account(
+        init,
+        payer = vault_authority,
+        space = 8 + PendingRateChange::INIT_SPACE,
+        seeds = [b"pending_rate", vault.key().as_ref()],
+        bump
+    )]
+    pub pending_rate_change: Account<'info, PendingRateChange>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ProposeRateChange<'info> {
+    /// Schedules a change of the mint's interest rate, executable once the vault's
+    /// rate change delay has elapsed.
+    pub fn propose_rate_change(&mut self, new_rate: i16, bump: u8) -> Result<()> {
+        require!(
+            new_rate >= self.vault.min_rate && new_rate <= self.vault.max_rate,
+            crate::errors::VaultError::RateOutOfBounds
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let executable_at = now
+            .checked_add(self.vault.effective_rate_change_delay(now))
+            .ok_or(crate::errors::VaultError::Overflow)?;
+
+        self.pending_rate_change.set_inner(PendingRateChange {
+            vault: self.vault.key(),
+            proposer: self.vault_authority.key(),
+            new_rate,
+            proposed_at: now,
+            executable_at,
+            bump,
+        });
+
+        msg!("Rate change to {} proposed, executable at {}", new_rate, executable_at);
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct ExecuteRateChange<'info> {
+    #[account(mut)]
+    pub vault_authority: Signer<'info>,
+
+    /// The vault account, it holds the mint's rate authority
+    #[account(
+        mut,
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// Role assignments of the vault, needed when the signer acts through a role
+    #[account(
+        seeds = [b"vault_roles", vault.key().as_ref()],
+        bump = vault_roles.bump,
+    )]
+    pub vault_roles: Option<Account<'info, VaultRoles>>,
+
+    /// The mint associated with the vault
+    #[account(mut, address = vault.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// The change to execute, closed once applied
+    #[account(
+        mut,
+        close = vault_authority,
+        seeds = [b"pending_rate", vault.key().as_ref()],
+        bump = pending_rate_change.bump,
+    )]
+    pub pending_rate_change: Account<'info, PendingRateChange>,
+
+    /// Every rate change made through this vault
+    #[account(
+        init_if_needed,
+        payer = vault_authority,
+        space = 8 + RateHistory::INIT_SPACE,
+        seeds = [b"rate_history", vault.key().as_ref()],
+        bump
+    )]
+    pub rate_history: Account<'info, RateHistory>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ExecuteRateChange<'info> {
+    /// Applies the pending rate change to the mint.
+    /// The mint's rate authority must have been handed to the vault beforehand.
+    /// Interest up to now is checkpointed at the old rate first, the mint can only
+    /// price past timestamps correctly up to its last rate update.
+    pub fn execute_rate_change(&mut self, rate_history_bump: u8) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            now >= self.pending_rate_change.executable_at,
+            crate::errors::VaultError::RateChangeTimelocked
+        );
+
+        // Bounds may have been tightened since the change was proposed
+        let new_rate = self.pending_rate_change.new_rate;
+        require!(
+            new_rate >= self.vault.min_rate && new_rate <= self.vault.max_rate,
+            crate::errors::VaultError::RateOutOfBounds
+        );
+
+        let config = interest_bearing_config(&self.mint.to_account_info())?;
+        require!(
+            Option::<Pubkey>::from(config.rate_authority) == Some(self.vault.key()),
+            crate::errors::VaultError::RateAuthorityNotDelegated
+        );
+        let old_rate = i16::from(config.current_rate);
+
+        checkpoint_interest(&mut self.vault, &self.mint.to_account_info())?;
+
+        let update_rate_ix = update_rate(
+            &self.token_program.key(),
+            &self.mint.key(),
+            &self.vault.key(),
+            &[],
+            new_rate,
+        )?;
+
+        let vault_index = self.vault.vault_index.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault".as_ref(),
+            self.vault.creator.as_ref(),
+            self.vault.mint.as_ref(),
+            vault_index.as_ref(),
+            &[self.vault.bump],
+        ]];
+
+        invoke_signed(
+            &update_rate_ix,
+            &[
+                self.mint.to_account_info(),
+                self.vault.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        if self.rate_history.vault == Pubkey::default() {
+            self.rate_history.vault = self.vault.key();
+            self.rate_history.bump = rate_history_bump;
+        }
+        self.rate_history.record(RateChange {
+            old_rate,
+            new_rate,
+            timestamp: now,
+            signer: self.vault_authority.key(),
+        })?;
+
+        msg!("Interest rate changed from {} to {}", old_rate, new_rate);
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct CancelRateChange<'info> {
+    #[account(mut)]
+    pub vault_authority: Signer<'info>,
+
+    /// The vault account - only the vault authority can cancel rate changes
+    #[account(
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// Role assignments of the vault, needed when the signer acts through a role
+    #[account(
+        seeds = [b"vault_roles", vault.key().as_ref()],
+        bump = vault_roles.bump,
+    )]
+    pub vault_roles: Option<Account<'info, VaultRoles>>,
+
+    /// The change to cancel
+    #[account(
+        mut,
+        close = vault_authority,
+        seeds = [b"pending_rate", vault.key().as_ref()],
+        bump = pending_rate_change.bump,
+    )]
+    pub pending_rate_change: Account<'info, PendingRateChange>,
+}
+
+impl<'info> CancelRateChange<'info> {
+    pub fn cancel_rate_change(&mut self) -> Result<()> {
+        msg!("Rate change to {} cancelled", self.pending_rate_change.new_rate);
+        Ok(())
+    }
+}
@@ -24,6 +24,9 @@ use anchor_spl::token_2022::spl_token_2022::{
     instruction::{initialize_mint2},
 };
 
+use crate::events::MintCreated;
+
+#[event_cpi]
 #[derive(Accounts)]
 pub struct TokenFactory<'info> {
     #[account(mut)]
@@ -48,7 +51,7 @@ pub struct TokenFactory<'info> {
 }
 
 impl<'info> TokenFactory<'info> {
-    pub fn init_mint(&mut self, interest_rate: i16) -> Result<()> {
+    pub fn init_mint(&mut self, interest_rate: i16) -> Result<MintCreated> {
         let decimals = 9;
 
         // Calculate space needed for mint with extensions
@@ -133,6 +136,13 @@ impl<'info> TokenFactory<'info> {
         )?;
 
         msg!("Mint initialized with transfer hook and interest bearing extensions");
-        Ok(())
+        Ok(MintCreated {
+            mint: self.mint.key(),
+            authority: self.user.key(),
+            transfer_hook_program: self.hook_program_id.key(),
+            interest_rate,
+            decimals,
+            timestamp: Clock::get()?.unix_timestamp,
+        })
     }
 }
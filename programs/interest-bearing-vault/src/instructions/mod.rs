@@ -1,10 +1,40 @@
 mod deposit;
+mod deposit_for;
 mod initialize;
 mod mint_operations;
 mod withdraw;
+mod admin_withdraw;
+mod realize_interest;
+mod interest_rate;
+mod vault_admin;
+mod pause;
+mod withdrawal_queue;
+mod collect_fees;
+mod close;
+mod accept_authority;
+mod multisig;
+mod roles;
+mod sync_reserve;
+mod emergency;
+mod batch;
 mod init_extra_account_meta;
 
 pub use deposit::*;
+pub use deposit_for::*;
 pub use initialize::*;
 pub use mint_operations::*;
 pub use withdraw::*;
+pub use admin_withdraw::*;
+pub use realize_interest::*;
+pub use interest_rate::*;
+pub use vault_admin::*;
+pub use pause::*;
+pub use withdrawal_queue::*;
+pub use collect_fees::*;
+pub use close::*;
+pub use accept_authority::*;
+pub use multisig::*;
+pub use roles::*;
+pub use sync_reserve::*;
+pub use emergency::*;
+pub use batch::*;
@@ -0,0 +1,56 @@
+use anchor_lang::prelude::*;
+
+use crate::state::*;
+
+#[derive(Accounts)]
+pub struct CreateMultisig<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// One-off key the multisig address is derived from, so no private key exists for it
+    pub create_key: Signer<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Multisig::INIT_SPACE,
+        seeds = [b"multisig", create_key.key().as_ref()],
+        bump
+    )]
+    pub multisig: Account<'info, Multisig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreateMultisig<'info> {
+    /// Creates a `threshold`-of-`signers` multisig, which can then be proposed as vault authority.
+    pub fn create_multisig(&mut self, signers: Vec<Pubkey>, threshold: u8, bump: u8) -> Result<()> {
+        require!(
+            !signers.is_empty() && signers.len() <= MAX_MULTISIG_SIGNERS,
+            crate::errors::VaultError::InvalidMultisig
+        );
+        require!(
+            threshold > 0 && threshold as usize <= signers.len(),
+            crate::errors::VaultError::InvalidMultisig
+        );
+        for (i, signer) in signers.iter().enumerate() {
+            require!(
+                !signers[..i].contains(signer),
+                crate::errors::VaultError::InvalidMultisig
+            );
+        }
+
+        self.multisig.signers = signers;
+        self.multisig.threshold = threshold;
+        self.multisig.create_key = self.create_key.key();
+        self.multisig.bump = bump;
+
+        msg!(
+            "Created {}-of-{} multisig {}",
+            threshold,
+            self.multisig.signers.len(),
+            self.multisig.key()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+use anchor_lang::prelude::*;
+
+use crate::state::*;
+
+#[derive(Accounts)]
+pub struct PauseVault<'info> {
+    pub pauser: Signer<'info>,
+
+    /// The vault account - the vault authority or a pauser can pause it
+    #[account(
+        mut,
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// Role assignments of the vault, needed when the signer acts through a role
+    #[account(
+        seeds = [b"vault_roles", vault.key().as_ref()],
+        bump = vault_roles.bump,
+    )]
+    pub vault_roles: Option<Account<'info, VaultRoles>>,
+}
+
+impl<'info> PauseVault<'info> {
+    /// Halts deposits and/or withdrawals, flags that are not set are left untouched.
+    pub fn pause_vault(&mut self, deposits: bool, withdrawals: bool) -> Result<()> {
+        if deposits {
+            self.vault.deposits_paused = true;
+        }
+        if withdrawals {
+            self.vault.withdrawals_paused = true;
+        }
+
+        msg!(
+            "Deposits paused: {}, withdrawals paused: {}",
+            self.vault.deposits_paused,
+            self.vault.withdrawals_paused
+        );
+        Ok(())
+    }
+
+    /// Resumes deposits and/or withdrawals, flags that are not set are left untouched.
+    pub fn unpause_vault(&mut self, deposits: bool, withdrawals: bool) -> Result<()> {
+        if deposits {
+            self.vault.deposits_paused = false;
+        }
+        if withdrawals {
+            self.vault.withdrawals_paused = false;
+        }
+
+        msg!(
+            "Deposits paused: {}, withdrawals paused: {}",
+            self.vault.deposits_paused,
+            self.vault.withdrawals_paused
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_spl::token_interface::{
+    Mint, TokenAccount, TokenInterface,
+    mint_to, MintTo,
+};
+
+use crate::state::*;
+use crate::utils::*;
+
+#[derive(Accounts)]
+pub struct RealizeInterest<'info> {
+    /// The vault account
+    #[account(
+        mut,
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// The mint associated with the vault, its mint authority must be handed to this
+    /// vault's `mint_authority`, so only that one vault can mint interest
+    #[account(
+        mut,
+        address = vault.mint,
+        constraint = mint.mint_authority == COption::Some(mint_authority.key()) @ crate::errors::VaultError::MintAuthorityNotDelegated
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Program PDA holding the mint authority for this vault, only used as a signer
+    #[account(
+        seeds = [b"mint_authority", vault.key().as_ref()],
+        bump
+    )]
+    pub mint_authority: UncheckedAccount<'info>,
+
+    /// The vault's token reserve account
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> RealizeInterest<'info> {
+    /// Mints the interest the reserve earned since the last realization into the reserve,
+    /// so the raw balance catches up with the interest-inclusive balance wallets display.
+    /// Interest is checkpointed whenever the reserve changes, so tokens that joined it
+    /// since the last realization only earn for the time they were there.
+    pub fn realize_interest(&mut self, mint_authority_bump: u8) -> Result<()> {
+        checkpoint_interest(&mut self.vault, &self.mint.to_account_info())?;
+        self.vault.absorb_reserve_surplus(self.vault_token_reserve.amount)?;
+
+        let interest = self.vault.unrealized_interest;
+        if interest == 0 {
+            msg!("No interest to realize");
+            return Ok(());
+        }
+        self.vault.unrealized_interest = 0;
+
+        let vault_key = self.vault.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"mint_authority".as_ref(),
+            vault_key.as_ref(),
+            &[mint_authority_bump],
+        ]];
+
+        mint_to(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                MintTo {
+                    mint: self.mint.to_account_info(),
+                    to: self.vault_token_reserve.to_account_info(),
+                    authority: self.mint_authority.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            interest,
+        )?;
+
+        // The performance fee is taken out of the realized interest
+        let fee = fee_amount(interest, self.vault.performance_fee_bps)?;
+        self.vault.token_reserve_amount = self.vault.token_reserve_amount
+            .checked_add(interest - fee)
+            .ok_or(crate::errors::VaultError::Overflow)?;
+        self.vault.accrue_fee(fee)?;
+
+        msg!("Realized {} tokens of interest", interest);
+        msg!("Total vault balance: {}", self.vault.token_reserve_amount);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+use anchor_lang::prelude::*;
+
+use crate::state::*;
+
+#[derive(Accounts)]
+pub struct ManageRoles<'info> {
+    #[account(mut)]
+    pub vault_authority: Signer<'info>,
+
+    /// The vault account - only the vault authority can grant and revoke roles
+    #[account(
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// Role assignments of the vault
+    #[account(
+        init_if_needed,
+        payer = vault_authority,
+        space = 8 + VaultRoles::INIT_SPACE,
+        seeds = [b"vault_roles", vault.key().as_ref()],
+        bump
+    )]
+    pub vault_roles: Account<'info, VaultRoles>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ManageRoles<'info> {
+    pub fn grant_role(&mut self, role: Role, member: Pubkey, bump: u8) -> Result<()> {
+        if self.vault_roles.vault == Pubkey::default() {
+            self.vault_roles.vault = self.vault.key();
+            self.vault_roles.bump = bump;
+        }
+
+        if !self.vault_roles.has_role(&member, role) {
+            require!(
+                self.vault_roles.assignments.len() < MAX_ROLE_MEMBERS,
+                crate::errors::VaultError::TooManyRoleMembers
+            );
+            self.vault_roles.assignments.push(RoleAssignment { member, role });
+        }
+
+        msg!("Granted {:?} role to {}", role, member);
+        Ok(())
+    }
+
+    pub fn revoke_role(&mut self, role: Role, member: Pubkey) -> Result<()> {
+        self.vault_roles
+            .assignments
+            .retain(|a| !(a.member == member && a.role == role));
+
+        msg!("Revoked {:?} role from {}", role, member);
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::state::*;
+use crate::utils::*;
+
+#[derive(Accounts)]
+pub struct SyncReserve<'info> {
+    /// The vault account - anyone can reconcile its books
+    #[account(
+        mut,
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// The mint associated with the vault
+    #[account(address = vault.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// The vault's token reserve account
+    #[account(
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> SyncReserve<'info> {
+    /// Reconciles the vault's books with the reserve balance. Tokens nobody deposited are
+    /// handled per the vault's surplus policy, a reserve holding less than the books fails.
+    pub fn sync_reserve(&mut self) -> Result<()> {
+        let balance = self.vault_token_reserve.amount;
+        let booked = self.vault.booked_reserve()?;
+        if balance < booked {
+            msg!("Reserve holds {} tokens, the vault accounts for {}", balance, booked);
+            return err!(crate::errors::VaultError::ReserveDeficit);
+        }
+
+        checkpoint_interest(&mut self.vault, &self.mint.to_account_info())?;
+        self.vault.absorb_reserve_surplus(balance)?;
+
+        msg!("Reserve synced, {} surplus tokens", balance - booked);
+        msg!("Total vault balance: {}", self.vault.token_reserve_amount);
+        msg!("Recoverable excess: {}", self.vault.excess_reserve);
+
+        Ok(())
+    }
+}
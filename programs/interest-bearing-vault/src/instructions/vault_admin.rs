@@ -0,0 +1,205 @@
+use anchor_lang::prelude::*;
+
+use crate::state::*;
+use crate::utils::*;
+
+#[derive(Accounts)]
+pub struct VaultAdmin<'info> {
+    pub vault_authority: Signer<'info>,
+
+    /// The vault account - only the vault authority can change its settings
+    #[account(
+        mut,
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// Role assignments of the vault, needed when the signer acts through a role
+    #[account(
+        seeds = [b"vault_roles", vault.key().as_ref()],
+        bump = vault_roles.bump,
+    )]
+    pub vault_roles: Option<Account<'info, VaultRoles>>,
+}
+
+impl<'info> VaultAdmin<'info> {
+    /// Proposes a new vault authority, which takes over once it calls `accept_authority`.
+    /// Proposing `Pubkey::default()` withdraws a pending proposal.
+    pub fn propose_authority(&mut self, new_authority: Pubkey) -> Result<()> {
+        self.vault.pending_authority = new_authority;
+
+        msg!("Proposed {} as vault authority", new_authority);
+        Ok(())
+    }
+
+    /// Limits the interest rates `propose_rate_change` accepts, in basis points.
+    pub fn set_rate_bounds(&mut self, min_rate: i16, max_rate: i16) -> Result<()> {
+        require!(min_rate <= max_rate, crate::errors::VaultError::InvalidRateBounds);
+
+        self.vault.min_rate = min_rate;
+        self.vault.max_rate = max_rate;
+
+        msg!("Rate bounds set to [{}, {}]", min_rate, max_rate);
+        Ok(())
+    }
+
+    /// Sets how long a proposed rate change waits before it can be executed.
+    /// Changes already proposed keep the delay they were proposed with. A longer delay
+    /// applies at once, a shorter one only after the current delay has elapsed, so it
+    /// cannot be used to rush a rate change through.
+    pub fn set_rate_change_delay(&mut self, delay: i64) -> Result<()> {
+        require!(delay >= 0, crate::errors::VaultError::InvalidRateChangeDelay);
+
+        let now = Clock::get()?.unix_timestamp;
+        let current = self.vault.effective_rate_change_delay(now);
+        self.vault.rate_change_delay = current;
+        self.vault.pending_rate_change_delay = 0;
+        self.vault.pending_rate_change_delay_at = 0;
+
+        if delay >= current {
+            self.vault.rate_change_delay = delay;
+
+            msg!("Rate change delay set to {} seconds", delay);
+        } else {
+            let effective_at = now
+                .checked_add(current)
+                .ok_or(crate::errors::VaultError::Overflow)?;
+            self.vault.pending_rate_change_delay = delay;
+            self.vault.pending_rate_change_delay_at = effective_at;
+
+            msg!("Rate change delay lowered to {} seconds, effective at {}", delay, effective_at);
+        }
+        Ok(())
+    }
+
+    /// Caps the vault's total deposits and each depositor's principal, 0 means unlimited.
+    /// Lowering a cap below current deposits only blocks new deposits.
+    pub fn set_deposit_caps(&mut self, max_total_deposits: u64, max_deposit_per_user: u64) -> Result<()> {
+        self.vault.max_total_deposits = max_total_deposits;
+        self.vault.max_deposit_per_user = max_deposit_per_user;
+
+        msg!("Deposit caps set - total: {}, per user: {}", max_total_deposits, max_deposit_per_user);
+        Ok(())
+    }
+
+    /// Sets how long a queued withdrawal waits after its request before it can be claimed.
+    pub fn set_withdrawal_cooldown(&mut self, cooldown: i64) -> Result<()> {
+        require!(cooldown >= 0, crate::errors::VaultError::InvalidWithdrawalCooldown);
+
+        self.vault.withdrawal_cooldown = cooldown;
+
+        msg!("Withdrawal cooldown set to {} seconds", cooldown);
+        Ok(())
+    }
+
+    /// Sets the deposit, withdraw and performance fees, in basis points, at most `MAX_FEE_BPS`.
+    /// Lower fees apply at once. Raising any fee gives depositors the same notice as a rate
+    /// change: the new fees wait the rate change delay and are then applied by `apply_fees`.
+    /// A later call replaces a pending increase.
+    pub fn set_fees(
+        &mut self,
+        deposit_fee_bps: u16,
+        withdraw_fee_bps: u16,
+        performance_fee_bps: u16,
+    ) -> Result<()> {
+        require!(
+            deposit_fee_bps <= MAX_FEE_BPS
+                && withdraw_fee_bps <= MAX_FEE_BPS
+                && performance_fee_bps <= MAX_FEE_BPS,
+            crate::errors::VaultError::InvalidFee
+        );
+
+        let raises_fees = deposit_fee_bps > self.vault.deposit_fee_bps
+            || withdraw_fee_bps > self.vault.withdraw_fee_bps
+            || performance_fee_bps > self.vault.performance_fee_bps;
+        if !raises_fees {
+            self.vault.deposit_fee_bps = deposit_fee_bps;
+            self.vault.withdraw_fee_bps = withdraw_fee_bps;
+            self.vault.performance_fee_bps = performance_fee_bps;
+            self.vault.pending_fees_at = 0;
+
+            msg!(
+                "Fees set - deposit: {}, withdraw: {}, performance: {}",
+                deposit_fee_bps,
+                withdraw_fee_bps,
+                performance_fee_bps
+            );
+            return Ok(());
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        let applicable_at = now
+            .checked_add(self.vault.effective_rate_change_delay(now))
+            .ok_or(crate::errors::VaultError::Overflow)?;
+        self.vault.pending_deposit_fee_bps = deposit_fee_bps;
+        self.vault.pending_withdraw_fee_bps = withdraw_fee_bps;
+        self.vault.pending_performance_fee_bps = performance_fee_bps;
+        self.vault.pending_fees_at = applicable_at;
+
+        msg!(
+            "Fees raised - deposit: {}, withdraw: {}, performance: {}, applicable at {}",
+            deposit_fee_bps,
+            withdraw_fee_bps,
+            performance_fee_bps,
+            applicable_at
+        );
+        Ok(())
+    }
+
+    /// Applies the fees raised by `set_fees` once their delay has elapsed.
+    pub fn apply_fees(&mut self) -> Result<()> {
+        require!(self.vault.pending_fees_at != 0, crate::errors::VaultError::NoPendingFees);
+        require!(
+            Clock::get()?.unix_timestamp >= self.vault.pending_fees_at,
+            crate::errors::VaultError::FeeIncreaseTimelocked
+        );
+
+        self.vault.deposit_fee_bps = self.vault.pending_deposit_fee_bps;
+        self.vault.withdraw_fee_bps = self.vault.pending_withdraw_fee_bps;
+        self.vault.performance_fee_bps = self.vault.pending_performance_fee_bps;
+        self.vault.pending_fees_at = 0;
+
+        msg!(
+            "Fees applied - deposit: {}, withdraw: {}, performance: {}",
+            self.vault.deposit_fee_bps,
+            self.vault.withdraw_fee_bps,
+            self.vault.performance_fee_bps
+        );
+        Ok(())
+    }
+
+    /// Sets how surplus reserve tokens are booked from now on,
+    /// excess already set aside stays recoverable.
+    pub fn set_surplus_policy(&mut self, surplus_policy: SurplusPolicy) -> Result<()> {
+        self.vault.surplus_policy = surplus_policy;
+
+        msg!("Surplus policy set to {:?}", surplus_policy);
+        Ok(())
+    }
+
+    /// Permanently stops deposits, withdrawals and admin withdrawals, share holders and queued
+    /// withdrawal requests can then leave with their pro-rata part of the reserve through
+    /// `emergency_withdraw` and `emergency_claim_withdraw`.
+    pub fn emergency_shutdown(&mut self) -> Result<()> {
+        require!(!self.vault.emergency_shutdown, crate::errors::VaultError::VaultShutdown);
+
+        self.vault.emergency_shutdown = true;
+
+        msg!("Vault shut down");
+        Ok(())
+    }
+
+    /// Sets the token account `collect_fees` pays accrued fees to.
+    pub fn set_fee_recipient(&mut self, fee_recipient: Pubkey) -> Result<()> {
+        self.vault.fee_recipient = fee_recipient;
+
+        msg!("Fee recipient set to {}", fee_recipient);
+        Ok(())
+    }
+}
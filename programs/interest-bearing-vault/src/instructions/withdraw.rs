@@ -1,49 +1,62 @@
-use std::error::Error;
-use anchor_lang::{prelude::*, solana_program::program::{invoke, invoke_signed},};
+use anchor_lang::prelude::*;
 use anchor_spl::{
-
     associated_token::AssociatedToken,
+    token_2022::Token2022,
     token_interface::{
-        Mint, TokenAccount, TokenInterface
+        Mint, TokenAccount, TokenInterface,
+        burn, Burn,
     }
 };
 
+use crate::events::WithdrawEvent;
 use crate::state::*;
+use crate::utils::*;
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct Withdraw<'info> {
     #[account(mut)]
     pub withdrawer: Signer<'info>,
 
-    /// The vault account - only the vault authority can withdraw
+    /// The vault account
     #[account(
         mut,
-        seeds = [b"vault", vault.vault_authority.as_ref()],
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
         bump = vault.bump,
-        constraint = vault.vault_authority == withdrawer.key() @ crate::errors::VaultError::Unauthorized
+        constraint = !vault.withdrawals_paused @ crate::errors::VaultError::VaultPaused,
+        constraint = !vault.emergency_shutdown @ crate::errors::VaultError::VaultShutdown
     )]
     pub vault: Account<'info, Vault>,
 
-
+    /// The withdrawer's position - only its owner can withdraw from it
     #[account(
         mut,
-        // constraint =  withdrawer,
         seeds = [b"vault_registry", vault.key().as_ref(), withdrawer.key().as_ref()],
-        bump,
+        bump = vault_registry_entry.bump,
+        constraint = vault_registry_entry.user == withdrawer.key() @ crate::errors::VaultError::Unauthorized
     )]
     pub vault_registry_entry: Account<'info, VaultRegistryEntry>,
 
     /// The mint associated with the vault
+    #[account(address = vault.mint)]
     pub mint: InterfaceAccount<'info, Mint>,
 
-    /// The withdrawer's token account (must be vault authority)
+    /// Token account the withdrawal is paid to, any account of the vault's mint
     #[account(
         mut,
-        associated_token::mint = mint,
-        associated_token::authority = withdrawer,
-        associated_token::token_program = token_program,
+        token::mint = mint,
+        token::token_program = token_program,
     )]
-    pub withdrawer_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Whitelist account for the recipient's owner, only exists once the transfer hook whitelisted it,
+    /// checked in the handler for mints with a transfer hook
+    pub recipient_whitelist: Option<UncheckedAccount<'info>>,
 
     /// The vault's token reserve account
     #[account(
@@ -54,124 +67,204 @@ pub struct Withdraw<'info> {
     )]
     pub vault_token_reserve: InterfaceAccount<'info, TokenAccount>,
 
-    /// CHECK: ExtraAccountMetaList account for transfer hook
+    /// The vault's share mint
     #[account(
-        seeds = [b"extra-account-metas", mint.key().as_ref()],
-        bump,
-        seeds::program = transfer_hook_program.key()
+        mut,
+        seeds = [b"share_mint", vault.key().as_ref()],
+        bump = vault.share_mint_bump,
     )]
-    pub extra_account_meta_list: UncheckedAccount<'info>,
-
-    /// CHECK: Transfer hook program
-    pub transfer_hook_program: UncheckedAccount<'info>,
+    pub share_mint: InterfaceAccount<'info, Mint>,
 
-    /// CHECK: Whitelist account for withdrawer (resolved via transfer hook)
+    /// The withdrawer's share token account, shares are burned from here
     #[account(
-        seeds = [b"whitelist", mint.key().as_ref(), withdrawer.key().as_ref()],
-        bump,
-        seeds::program = transfer_hook_program.key()
+        mut,
+        associated_token::mint = share_mint,
+        associated_token::authority = withdrawer,
+        associated_token::token_program = share_token_program,
     )]
-    pub withdrawer_whitelist_PDA: UncheckedAccount<'info>,
+    pub withdrawer_share_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: ExtraAccountMetaList account for transfer hook, checked against the mint's hook
+    pub extra_account_meta_list: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Transfer hook program, only needed for mints with a transfer hook
+    pub transfer_hook_program: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Whitelist account for the vault, the owner of the reserve (resolved via transfer hook)
+    pub vault_whitelist: Option<UncheckedAccount<'info>>,
 
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Interface<'info, TokenInterface>,
+    pub share_token_program: Program<'info, Token2022>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> Withdraw<'info> {
-    pub fn withdraw(&mut self, amount: u64) -> Result<()> {
+    /// Withdraws `amount` tokens, burning as many shares as they are currently worth.
+    /// For mints charging a transfer fee, `max_fee` bounds the fee taken from the payout.
+    pub fn withdraw(&mut self, amount: u64, max_fee: u64) -> Result<WithdrawEvent> {
         require!(amount > 0, crate::errors::VaultError::InvalidAmount);
+
+        checkpoint_interest(&mut self.vault, &self.mint.to_account_info())?;
+        self.vault.absorb_reserve_surplus(self.vault_token_reserve.amount)?;
+        let total_assets = self.vault.token_reserve_amount;
+        let total_shares = self.share_mint.supply;
+
+        let position_value = convert_to_assets(
+            self.vault_registry_entry.shares,
+            total_assets,
+            total_shares,
+            Rounding::Down,
+        )?;
+        require!(
+            position_value >= amount,
+            crate::errors::VaultError::InsufficientFunds
+        );
+
+        // Round up so the vault never hands out more than the burned shares are worth
+        let shares = convert_to_shares(amount, total_assets, total_shares, Rounding::Up)?;
+
+        self.settle(amount, shares, max_fee)
+    }
+
+    /// Burns `shares` and pays out the tokens they are currently worth.
+    pub fn redeem(&mut self, shares: u64, max_fee: u64) -> Result<WithdrawEvent> {
+        require!(shares > 0, crate::errors::VaultError::InvalidAmount);
         require!(
-            self.vault_registry_entry.token_balance >= amount,
+            self.vault_registry_entry.shares >= shares,
             crate::errors::VaultError::InsufficientFunds
         );
+
+        checkpoint_interest(&mut self.vault, &self.mint.to_account_info())?;
+        self.vault.absorb_reserve_surplus(self.vault_token_reserve.amount)?;
+        let amount = convert_to_assets(
+            shares,
+            self.vault.token_reserve_amount,
+            self.share_mint.supply,
+            Rounding::Down,
+        )?;
+        require!(amount > 0, crate::errors::VaultError::InvalidAmount);
+
+        self.settle(amount, shares, max_fee)
+    }
+
+    /// Takes `amount` out of the position, the withdraw fee is deducted from the payout.
+    /// The reserve pays out in full, any transfer fee comes out of what the recipient gets.
+    fn settle(&mut self, amount: u64, shares: u64, max_fee: u64) -> Result<WithdrawEvent> {
+        // Hooked mints only pay out to whitelisted owners
+        if let Some(hook_program_id) = transfer_hook_program_id(&self.mint.to_account_info())? {
+            require!(
+                self.recipient_whitelist.as_ref().is_some_and(|whitelist| {
+                    is_whitelisted(whitelist, &hook_program_id, &self.mint.key(), &self.recipient_token_account.owner)
+                }),
+                crate::errors::VaultError::RecipientNotWhitelisted
+            );
+        }
         require!(
             self.vault.token_reserve_amount >= amount,
             crate::errors::VaultError::InsufficientFunds
         );
 
+        let fee = fee_amount(amount, self.vault.withdraw_fee_bps)?;
+        let payout = amount
+            .checked_sub(fee)
+            .ok_or(crate::errors::VaultError::Underflow)?;
+        require!(
+            self.vault.available_liquidity(self.vault_token_reserve.amount) >= payout,
+            crate::errors::VaultError::InsufficientLiquidity
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            now >= self.vault_registry_entry.locked_until,
+            crate::errors::VaultError::PositionLocked
+        );
 
-        // Transfer tokens from vault reserve to withdrawer using SPL Token-2022 onchain helper
-        // This properly handles transfer hooks by including additional accounts
-        let vault_authority = self.vault.vault_authority;
-        let bump = &[self.vault.bump];
-        let seeds = &[
-            b"vault".as_ref(),
-            vault_authority.as_ref(),
-            bump,
-        ];
-
-        // IMPORTANT: Only pass EXTRA accounts (not source/mint/dest/authority) in additional_accounts
-        // let additional_accounts = vec![
-        //     self.extra_account_meta_list.to_account_info(),
-        //     self.withdrawer_whitelist_PDA.to_account_info(),
-        //     self.transfer_hook_program.to_account_info(),
-        // ];
-        //
-
-        let v_signer_meta = AccountMeta::new(self.vault.key(), true);
-
-        let mut transfer_ix =  spl_token_2022::instruction::transfer_checked(
-            &self.token_program.key(),
-            &self.vault_token_reserve.key(),
-            &self.mint.key(),
-            &self.withdrawer_token_account.key(),
-            &self.vault.key(),
-            &[&self.withdrawer_token_account.key()],
-            amount,
-            self.mint.decimals,
-        ).map_err(|e:ProgramError|{msg!("Unable to invoke instruction: {:?}", e); e } )?;
-
-        transfer_ix.accounts.push(AccountMeta::new_readonly(self.extra_account_meta_list.key(), false));
-        transfer_ix.accounts.push(AccountMeta::new(self.withdrawer_whitelist_PDA.key(), false));
-
-        let account_infos = &[
-            self.vault_token_reserve.to_account_info(),
-            self.mint.to_account_info(),
-            self.withdrawer_token_account.to_account_info(),
-            self.vault.to_account_info(),
-            self.token_program.to_account_info(), // The Token Program must be in this list for `invoke`
-            self.extra_account_meta_list.to_account_info(),
-            self.withdrawer_whitelist_PDA.to_account_info(),
-            self.transfer_hook_program.to_account_info(),
-        ];
-
-        msg!("On to invoke instruction.");
-
-        invoke_signed(&transfer_ix, account_infos, &[seeds])?;
-
-        // spl_token_2022::onchain::invoke_transfer_checked(
-        //     &self.token_program.key(),
-        //     self.vault_token_reserve.to_account_info(),
-        //     self.mint.to_account_info(),
-        //     self.withdrawer_token_account.to_account_info(),
-        //     self.vault.to_account_info(),
-        //     &[
-        //         self.extra_account_meta_list.to_account_info(),
-        //         self.withdrawer_whitelist_PDA.to_account_info(),
-        //         self.transfer_hook_program.to_account_info(),
-        //     ],
-        //     amount,
-        //     self.mint.decimals,
-        //     &[],
-        // ).map_err(|e:ProgramError|{msg!("Unable to invoke instruction: {:?}", e); e } )?;
+        // Record interest earned on the position before it shrinks
+        let position_value = convert_to_assets(
+            self.vault_registry_entry.shares,
+            self.vault.token_reserve_amount,
+            self.share_mint.supply,
+            Rounding::Down,
+        )?;
+        let interest_inclusive_balance =
+            interest_inclusive_amount(&self.mint.to_account_info(), position_value, now)?;
+        self.vault_registry_entry.accrue_interest(interest_inclusive_balance, now)?;
+
+        burn(
+            CpiContext::new(
+                self.share_token_program.to_account_info(),
+                Burn {
+                    mint: self.share_mint.to_account_info(),
+                    from: self.withdrawer_share_account.to_account_info(),
+                    authority: self.withdrawer.to_account_info(),
+                },
+            ),
+            shares,
+        )?;
+
+        let transfer_fee = transfer_from_reserve_with_max_fee(
+            &self.token_program,
+            &self.vault,
+            &self.vault_token_reserve,
+            &self.mint,
+            self.recipient_token_account.to_account_info(),
+            &hook_accounts(
+                &self.mint,
+                self.extra_account_meta_list.as_ref(),
+                self.vault_whitelist.as_ref(),
+                self.transfer_hook_program.as_ref(),
+            )?,
+            payout,
+            max_fee,
+        )?;
 
         // Update vault state
         self.vault.token_reserve_amount = self.vault.token_reserve_amount
             .checked_sub(amount)
             .ok_or(crate::errors::VaultError::Underflow)?;
+        self.vault.accrue_fee(fee)?;
 
-        msg!("Withdrew {} tokens from vault", amount);
+        msg!(
+            "Withdrew {} tokens from vault to {}, {} withheld as transfer fee",
+            payout,
+            self.recipient_token_account.key(),
+            transfer_fee
+        );
         msg!("Remaining vault balance: {}", self.vault.token_reserve_amount);
 
-        // Update vault registry
+        // Update vault registry, anything paid out beyond the principal is yield
         self.vault_registry_entry.token_balance = self.vault_registry_entry.token_balance
-                .checked_sub(amount)
-                .ok_or(crate::errors::VaultError::Overflow)?;
+                .saturating_sub(amount);
+        self.vault_registry_entry.shares = self.vault_registry_entry.shares
+                .checked_sub(shares)
+                .ok_or(crate::errors::VaultError::Underflow)?;
         self.vault_registry_entry.num_withdraws = self.vault_registry_entry.num_withdraws
                 .checked_add(1)
                 .ok_or(crate::errors::VaultError::Overflow)?;
+        self.vault.record_withdrawal(self.vault_registry_entry.shares)?;
 
-        Ok(())
+        let position_value = convert_to_assets(
+            self.vault_registry_entry.shares,
+            self.vault.token_reserve_amount,
+            self.share_mint.supply
+                .checked_sub(shares)
+                .ok_or(crate::errors::VaultError::Underflow)?,
+            Rounding::Down,
+        )?;
+        self.vault_registry_entry.interest_inclusive_balance =
+            interest_inclusive_amount(&self.mint.to_account_info(), position_value, now)?;
+
+        Ok(WithdrawEvent {
+            vault: self.vault.key(),
+            withdrawer: self.withdrawer.key(),
+            amount,
+            fee,
+            shares_burned: shares,
+            user_shares_after: self.vault_registry_entry.shares,
+            user_balance_after: self.vault_registry_entry.token_balance,
+            vault_balance_after: self.vault.token_reserve_amount,
+            timestamp: now,
+        })
     }
 }
@@ -0,0 +1,373 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_2022::Token2022,
+    token_interface::{
+        Mint, TokenAccount, TokenInterface,
+        burn, Burn,
+    }
+};
+
+use crate::state::*;
+use crate::utils::*;
+
+#[derive(Accounts)]
+pub struct RequestWithdraw<'info> {
+    #[account(mut)]
+    pub withdrawer: Signer<'info>,
+
+    /// The vault account
+    #[account(
+        mut,
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+        constraint = !vault.withdrawals_paused @ crate::errors::VaultError::VaultPaused,
+        constraint = !vault.emergency_shutdown @ crate::errors::VaultError::VaultShutdown
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// The withdrawer's position - only its owner can request a withdrawal from it
+    #[account(
+        mut,
+        seeds = [b"vault_registry", vault.key().as_ref(), withdrawer.key().as_ref()],
+        bump = vault_registry_entry.bump,
+        constraint = vault_registry_entry.user == withdrawer.key() @ crate::errors::VaultError::Unauthorized
+    )]
+    pub vault_registry_entry: Account<'info, VaultRegistryEntry>,
+
+    /// The new withdrawal request, the withdrawer pays its rent and gets it back on claim
+    #[account(
+        init,
+        payer = withdrawer,
+        space = 8 + WithdrawalRequest::INIT_SPACE,
+        seeds = [
+            b"withdrawal_request",
+            vault.key().as_ref(),
+            vault.next_withdrawal_request_id.to_le_bytes().as_ref(),
+        ],
+        bump
+    )]
+    pub withdrawal_request: Account<'info, WithdrawalRequest>,
+
+    /// The mint associated with the vault
+    #[account(address = vault.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// The vault's token reserve account
+    #[account(
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    /// The vault's share mint
+    #[account(
+        mut,
+        seeds = [b"share_mint", vault.key().as_ref()],
+        bump = vault.share_mint_bump,
+    )]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    /// The withdrawer's share token account, shares are burned from here
+    #[account(
+        mut,
+        associated_token::mint = share_mint,
+        associated_token::authority = withdrawer,
+        associated_token::token_program = share_token_program,
+    )]
+    pub withdrawer_share_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub share_token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> RequestWithdraw<'info> {
+    /// Burns `shares` and queues the tokens they are currently worth for a later claim.
+    /// The queued amount no longer earns vault yield.
+    pub fn request_withdraw(&mut self, shares: u64, request_bump: u8) -> Result<()> {
+        require!(shares > 0, crate::errors::VaultError::InvalidAmount);
+        require!(
+            self.vault_registry_entry.shares >= shares,
+            crate::errors::VaultError::InsufficientFunds
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            now >= self.vault_registry_entry.locked_until,
+            crate::errors::VaultError::PositionLocked
+        );
+
+        checkpoint_interest(&mut self.vault, &self.mint.to_account_info())?;
+        self.vault.absorb_reserve_surplus(self.vault_token_reserve.amount)?;
+        let total_assets = self.vault.token_reserve_amount;
+        let total_shares = self.share_mint.supply;
+
+        let amount = convert_to_assets(shares, total_assets, total_shares, Rounding::Down)?;
+        require!(amount > 0, crate::errors::VaultError::InvalidAmount);
+
+        // Record interest earned on the position before it shrinks
+        let position_value = convert_to_assets(
+            self.vault_registry_entry.shares,
+            total_assets,
+            total_shares,
+            Rounding::Down,
+        )?;
+        let interest_inclusive_balance =
+            interest_inclusive_amount(&self.mint.to_account_info(), position_value, now)?;
+        self.vault_registry_entry.accrue_interest(interest_inclusive_balance, now)?;
+
+        burn(
+            CpiContext::new(
+                self.share_token_program.to_account_info(),
+                Burn {
+                    mint: self.share_mint.to_account_info(),
+                    from: self.withdrawer_share_account.to_account_info(),
+                    authority: self.withdrawer.to_account_info(),
+                },
+            ),
+            shares,
+        )?;
+
+        // Move the owed amount out of the assets backing the shares, net of the withdraw fee
+        let fee = fee_amount(amount, self.vault.withdraw_fee_bps)?;
+        let owed = amount
+            .checked_sub(fee)
+            .ok_or(crate::errors::VaultError::Underflow)?;
+        self.vault.token_reserve_amount = self.vault.token_reserve_amount
+            .checked_sub(amount)
+            .ok_or(crate::errors::VaultError::Underflow)?;
+        self.vault.accrue_fee(fee)?;
+        self.vault.queued_withdrawals = self.vault.queued_withdrawals
+            .checked_add(owed)
+            .ok_or(crate::errors::VaultError::Overflow)?;
+
+        let id = self.vault.next_withdrawal_request_id;
+        self.vault.next_withdrawal_request_id = id
+            .checked_add(1)
+            .ok_or(crate::errors::VaultError::Overflow)?;
+        self.withdrawal_request.set_inner(WithdrawalRequest {
+            vault: self.vault.key(),
+            id,
+            user: self.withdrawer.key(),
+            amount: owed,
+            requested_at: now,
+            fulfilled: false,
+            bump: request_bump,
+        });
+
+        // Update vault registry
+        self.vault_registry_entry.token_balance = self.vault_registry_entry.token_balance
+                .saturating_sub(amount);
+        self.vault_registry_entry.shares = self.vault_registry_entry.shares
+                .checked_sub(shares)
+                .ok_or(crate::errors::VaultError::Underflow)?;
+        self.vault_registry_entry.num_withdraws = self.vault_registry_entry.num_withdraws
+                .checked_add(1)
+                .ok_or(crate::errors::VaultError::Overflow)?;
+        self.vault.record_withdrawal(self.vault_registry_entry.shares)?;
+
+        let position_value = convert_to_assets(
+            self.vault_registry_entry.shares,
+            self.vault.token_reserve_amount,
+            total_shares
+                .checked_sub(shares)
+                .ok_or(crate::errors::VaultError::Underflow)?,
+            Rounding::Down,
+        )?;
+        self.vault_registry_entry.interest_inclusive_balance =
+            interest_inclusive_amount(&self.mint.to_account_info(), position_value, now)?;
+
+        msg!("Withdrawal request {} queued for {} tokens", id, owed);
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct FulfilWithdrawals<'info> {
+    pub vault_authority: Signer<'info>,
+
+    /// The vault account - only the vault authority can fulfil queued withdrawals
+    #[account(
+        mut,
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// Role assignments of the vault, needed when the signer acts through a role
+    #[account(
+        seeds = [b"vault_roles", vault.key().as_ref()],
+        bump = vault_roles.bump,
+    )]
+    pub vault_roles: Option<Account<'info, VaultRoles>>,
+
+    /// The vault's token reserve account
+    #[account(
+        address = vault.token_reserve,
+    )]
+    pub vault_token_reserve: InterfaceAccount<'info, TokenAccount>,
+}
+
+impl<'info> FulfilWithdrawals<'info> {
+    /// Sets reserve tokens aside for `requests`, which must be the oldest unfulfilled
+    /// requests in id order, stopping at the first request the reserve cannot cover.
+    pub fn fulfil_withdrawals(&mut self, requests: &'info [AccountInfo<'info>]) -> Result<()> {
+        let mut available = self.vault.available_liquidity(self.vault_token_reserve.amount);
+        let mut fulfilled = 0u64;
+
+        for request_info in requests {
+            let mut request = Account::<WithdrawalRequest>::try_from(request_info)?;
+            require_keys_eq!(request.vault, self.vault.key(), crate::errors::VaultError::WithdrawalRequestNotFound);
+            require!(
+                request.id == self.vault.next_withdrawal_to_fulfil,
+                crate::errors::VaultError::WithdrawalRequestOutOfOrder
+            );
+            if request.amount > available {
+                break;
+            }
+            available -= request.amount;
+            request.fulfilled = true;
+            request.exit(&crate::ID)?;
+            self.vault.next_withdrawal_to_fulfil = request.id
+                .checked_add(1)
+                .ok_or(crate::errors::VaultError::Overflow)?;
+            fulfilled = fulfilled
+                .checked_add(request.amount)
+                .ok_or(crate::errors::VaultError::Overflow)?;
+        }
+
+        self.vault.fulfilled_withdrawals = self.vault.fulfilled_withdrawals
+            .checked_add(fulfilled)
+            .ok_or(crate::errors::VaultError::Overflow)?;
+
+        msg!("Fulfilled {} tokens of queued withdrawals", fulfilled);
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(request_id: u64)]
+pub struct ClaimWithdraw<'info> {
+    #[account(mut)]
+    pub withdrawer: Signer<'info>,
+
+    /// The vault account
+    #[account(
+        mut,
+        seeds = [
+            b"vault",
+            vault.creator.as_ref(),
+            vault.mint.as_ref(),
+            vault.vault_index.to_le_bytes().as_ref(),
+        ],
+        bump = vault.bump,
+        constraint = !vault.withdrawals_paused @ crate::errors::VaultError::VaultPaused,
+        constraint = !vault.emergency_shutdown @ crate::errors::VaultError::VaultShutdown
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// The claimed request, closed to the withdrawer once paid out
+    #[account(
+        mut,
+        close = withdrawer,
+        seeds = [b"withdrawal_request", vault.key().as_ref(), request_id.to_le_bytes().as_ref()],
+        bump = withdrawal_request.bump,
+        constraint = withdrawal_request.user == withdrawer.key() @ crate::errors::VaultError::WithdrawalRequestNotFound
+    )]
+    pub withdrawal_request: Account<'info, WithdrawalRequest>,
+
+    /// The mint associated with the vault
+    #[account(address = vault.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// The withdrawer's token account
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = withdrawer,
+        associated_token::token_program = token_program,
+    )]
+    pub withdrawer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// The vault's token reserve account
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: ExtraAccountMetaList account for transfer hook, checked against the mint's hook
+    pub extra_account_meta_list: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Transfer hook program, only needed for mints with a transfer hook
+    pub transfer_hook_program: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Whitelist account for the vault, the owner of the reserve (resolved via transfer hook)
+    pub vault_whitelist: Option<UncheckedAccount<'info>>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ClaimWithdraw<'info> {
+    /// Pays out a fulfilled withdrawal request once the vault's cooldown has elapsed.
+    /// Any transfer fee, at most `max_fee`, comes out of what the withdrawer gets.
+    pub fn claim_withdraw(&mut self, request_id: u64, max_fee: u64) -> Result<()> {
+        let request = self.withdrawal_request.clone().into_inner();
+
+        require!(request.fulfilled, crate::errors::VaultError::WithdrawalNotFulfilled);
+        let claimable_at = request.requested_at
+            .checked_add(self.vault.withdrawal_cooldown)
+            .ok_or(crate::errors::VaultError::Overflow)?;
+        require!(
+            Clock::get()?.unix_timestamp >= claimable_at,
+            crate::errors::VaultError::WithdrawalCooldown
+        );
+
+        let transfer_fee = transfer_from_reserve_with_max_fee(
+            &self.token_program,
+            &self.vault,
+            &self.vault_token_reserve,
+            &self.mint,
+            self.withdrawer_token_account.to_account_info(),
+            &hook_accounts(
+                &self.mint,
+                self.extra_account_meta_list.as_ref(),
+                self.vault_whitelist.as_ref(),
+                self.transfer_hook_program.as_ref(),
+            )?,
+            request.amount,
+            max_fee,
+        )?;
+
+        self.vault.queued_withdrawals = self.vault.queued_withdrawals
+            .checked_sub(request.amount)
+            .ok_or(crate::errors::VaultError::Underflow)?;
+        self.vault.fulfilled_withdrawals = self.vault.fulfilled_withdrawals
+            .checked_sub(request.amount)
+            .ok_or(crate::errors::VaultError::Underflow)?;
+
+        msg!(
+            "Claimed withdrawal request {} for {} tokens, {} withheld as transfer fee",
+            request_id,
+            request.amount,
+            transfer_fee
+        );
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
 mod state;
 mod instructions;
 mod errors;
+mod utils;
+mod events;
 
 #[cfg(test)]
 mod tests;
@@ -11,6 +13,8 @@ declare_id!("GaHcCA1SB8gjXCBG6ZDDo9d8j8F8fz5cRsSYgfhkuDzp");
 
 use instructions::*;
 use errors::*;
+use state::{Role, SurplusPolicy};
+use utils::{check_authority, check_role};
 
 #[program]
 pub mod interest_bearing_vault {
@@ -20,18 +24,333 @@ pub mod interest_bearing_vault {
         ctx: Context<TokenFactory>,
         interest_rate: i16,
     ) -> Result<()> {
-        ctx.accounts.init_mint(interest_rate)
+        let event = ctx.accounts.init_mint(interest_rate)?;
+        emit_cpi!(event);
+        Ok(())
     }
 
-    pub fn initialize_vault(ctx: Context<InitializeVault>) -> Result<()> {
-        ctx.accounts.initialize_vault(ctx.bumps.vault)
+    pub fn create_multisig(ctx: Context<CreateMultisig>, signers: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        ctx.accounts.create_multisig(signers, threshold, ctx.bumps.multisig)
     }
 
-    pub fn deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
-        ctx.accounts.deposit(amount, ctx.bumps.vault_registry_entry)
+    pub fn initialize_vault(ctx: Context<InitializeVault>, vault_index: u64) -> Result<()> {
+        let event = ctx.accounts.initialize_vault(vault_index, ctx.bumps.vault, ctx.bumps.share_mint)?;
+        emit_cpi!(event);
+        Ok(())
     }
 
-    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
-        ctx.accounts.withdraw(amount)
+    pub fn deposit(ctx: Context<Deposit>, amount: u64, lock_duration: i64, max_fee: u64) -> Result<()> {
+        let event = ctx.accounts.deposit(amount, lock_duration, max_fee, ctx.bumps.vault_registry_entry)?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn deposit_for(ctx: Context<DepositFor>, amount: u64, max_fee: u64) -> Result<()> {
+        let event = ctx.accounts.deposit_for(amount, max_fee, ctx.bumps.vault_registry_entry)?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn batch_deposit<'info>(
+        ctx: Context<'_, '_, '_, 'info, BatchDeposit<'info>>,
+        amounts: Vec<u64>,
+    ) -> Result<()> {
+        let events = ctx.accounts.batch_deposit(&amounts, ctx.remaining_accounts)?;
+        for event in events {
+            emit_cpi!(event);
+        }
+        Ok(())
+    }
+
+    pub fn withdraw(ctx: Context<Withdraw>, amount: u64, max_fee: u64) -> Result<()> {
+        let event = ctx.accounts.withdraw(amount, max_fee)?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn redeem(ctx: Context<Withdraw>, shares: u64, max_fee: u64) -> Result<()> {
+        let event = ctx.accounts.redeem(shares, max_fee)?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn emergency_withdraw(ctx: Context<EmergencyWithdraw>, max_fee: u64) -> Result<()> {
+        let event = ctx.accounts.emergency_withdraw(max_fee)?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn emergency_claim_withdraw(
+        ctx: Context<EmergencyClaimWithdraw>,
+        request_id: u64,
+        max_fee: u64,
+    ) -> Result<()> {
+        ctx.accounts.emergency_claim_withdraw(request_id, max_fee)
+    }
+
+    pub fn request_withdraw(ctx: Context<RequestWithdraw>, shares: u64) -> Result<()> {
+        ctx.accounts.request_withdraw(shares, ctx.bumps.withdrawal_request)
+    }
+
+    pub fn fulfil_withdrawals<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FulfilWithdrawals<'info>>,
+        count: u8,
+    ) -> Result<()> {
+        let count = usize::from(count);
+        require!(count <= ctx.remaining_accounts.len(), VaultError::WithdrawalRequestNotFound);
+        let (requests, approvals) = ctx.remaining_accounts.split_at(count);
+        check_role(
+            &ctx.accounts.vault,
+            ctx.accounts.vault_roles.as_deref(),
+            Role::Operator,
+            &ctx.accounts.vault_authority,
+            approvals,
+        )?;
+        ctx.accounts.fulfil_withdrawals(requests)
+    }
+
+    pub fn claim_withdraw(ctx: Context<ClaimWithdraw>, request_id: u64, max_fee: u64) -> Result<()> {
+        ctx.accounts.claim_withdraw(request_id, max_fee)
+    }
+
+    pub fn admin_withdraw(ctx: Context<AdminWithdraw>, amount: u64) -> Result<()> {
+        check_authority(ctx.accounts.vault.vault_authority, &ctx.accounts.vault_authority, ctx.remaining_accounts)?;
+        ctx.accounts.admin_withdraw(amount)
+    }
+
+    pub fn batch_payout<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BatchPayout<'info>>,
+        amounts: Vec<u64>,
+        max_fee: u64,
+    ) -> Result<()> {
+        let recipients_len = amounts.len()
+            .checked_mul(BATCH_PAYOUT_ACCOUNTS)
+            .filter(|len| *len <= ctx.remaining_accounts.len())
+            .ok_or(VaultError::InvalidBatch)?;
+        let (recipients, approvals) = ctx.remaining_accounts.split_at(recipients_len);
+        check_authority(ctx.accounts.vault.vault_authority, &ctx.accounts.vault_authority, approvals)?;
+        ctx.accounts.batch_payout(&amounts, max_fee, recipients)
+    }
+
+    pub fn recover_excess(ctx: Context<AdminWithdraw>) -> Result<()> {
+        check_authority(ctx.accounts.vault.vault_authority, &ctx.accounts.vault_authority, ctx.remaining_accounts)?;
+        ctx.accounts.recover_excess()
+    }
+
+    pub fn sync_reserve(ctx: Context<SyncReserve>) -> Result<()> {
+        ctx.accounts.sync_reserve()
+    }
+
+    pub fn close_position(ctx: Context<ClosePosition>) -> Result<()> {
+        ctx.accounts.close_position()
+    }
+
+    pub fn close_vault(ctx: Context<CloseVault>) -> Result<()> {
+        check_role(
+            &ctx.accounts.vault,
+            ctx.accounts.vault_roles.as_deref(),
+            Role::Admin,
+            &ctx.accounts.vault_authority,
+            ctx.remaining_accounts,
+        )?;
+        ctx.accounts.close_vault()
+    }
+
+    pub fn collect_fees(ctx: Context<CollectFees>) -> Result<()> {
+        check_role(
+            &ctx.accounts.vault,
+            ctx.accounts.vault_roles.as_deref(),
+            Role::FeeCollector,
+            &ctx.accounts.vault_authority,
+            ctx.remaining_accounts,
+        )?;
+        ctx.accounts.collect_fees()
+    }
+
+    pub fn realize_interest(ctx: Context<RealizeInterest>) -> Result<()> {
+        ctx.accounts.realize_interest(ctx.bumps.mint_authority)
+    }
+
+    pub fn propose_rate_change(ctx: Context<ProposeRateChange>, new_rate: i16) -> Result<()> {
+        check_role(
+            &ctx.accounts.vault,
+            ctx.accounts.vault_roles.as_deref(),
+            Role::RateSetter,
+            &ctx.accounts.vault_authority,
+            ctx.remaining_accounts,
+        )?;
+        ctx.accounts.propose_rate_change(new_rate, ctx.bumps.pending_rate_change)
+    }
+
+    pub fn execute_rate_change(ctx: Context<ExecuteRateChange>) -> Result<()> {
+        check_role(
+            &ctx.accounts.vault,
+            ctx.accounts.vault_roles.as_deref(),
+            Role::RateSetter,
+            &ctx.accounts.vault_authority,
+            ctx.remaining_accounts,
+        )?;
+        ctx.accounts.execute_rate_change(ctx.bumps.rate_history)
+    }
+
+    pub fn cancel_rate_change(ctx: Context<CancelRateChange>) -> Result<()> {
+        check_role(
+            &ctx.accounts.vault,
+            ctx.accounts.vault_roles.as_deref(),
+            Role::RateSetter,
+            &ctx.accounts.vault_authority,
+            ctx.remaining_accounts,
+        )?;
+        ctx.accounts.cancel_rate_change()
+    }
+
+    pub fn propose_authority(ctx: Context<VaultAdmin>, new_authority: Pubkey) -> Result<()> {
+        check_authority(ctx.accounts.vault.vault_authority, &ctx.accounts.vault_authority, ctx.remaining_accounts)?;
+        ctx.accounts.propose_authority(new_authority)
+    }
+
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        check_authority(ctx.accounts.vault.pending_authority, &ctx.accounts.new_authority, ctx.remaining_accounts)?;
+        ctx.accounts.accept_authority()
+    }
+
+    pub fn grant_role(ctx: Context<ManageRoles>, role: Role, member: Pubkey) -> Result<()> {
+        check_authority(ctx.accounts.vault.vault_authority, &ctx.accounts.vault_authority, ctx.remaining_accounts)?;
+        ctx.accounts.grant_role(role, member, ctx.bumps.vault_roles)
+    }
+
+    pub fn revoke_role(ctx: Context<ManageRoles>, role: Role, member: Pubkey) -> Result<()> {
+        check_authority(ctx.accounts.vault.vault_authority, &ctx.accounts.vault_authority, ctx.remaining_accounts)?;
+        ctx.accounts.revoke_role(role, member)
+    }
+
+    pub fn set_rate_bounds(ctx: Context<VaultAdmin>, min_rate: i16, max_rate: i16) -> Result<()> {
+        check_role(
+            &ctx.accounts.vault,
+            ctx.accounts.vault_roles.as_deref(),
+            Role::Admin,
+            &ctx.accounts.vault_authority,
+            ctx.remaining_accounts,
+        )?;
+        ctx.accounts.set_rate_bounds(min_rate, max_rate)
+    }
+
+    pub fn set_rate_change_delay(ctx: Context<VaultAdmin>, delay: i64) -> Result<()> {
+        check_role(
+            &ctx.accounts.vault,
+            ctx.accounts.vault_roles.as_deref(),
+            Role::Admin,
+            &ctx.accounts.vault_authority,
+            ctx.remaining_accounts,
+        )?;
+        ctx.accounts.set_rate_change_delay(delay)
+    }
+
+    pub fn set_deposit_caps(
+        ctx: Context<VaultAdmin>,
+        max_total_deposits: u64,
+        max_deposit_per_user: u64,
+    ) -> Result<()> {
+        check_role(
+            &ctx.accounts.vault,
+            ctx.accounts.vault_roles.as_deref(),
+            Role::Admin,
+            &ctx.accounts.vault_authority,
+            ctx.remaining_accounts,
+        )?;
+        ctx.accounts.set_deposit_caps(max_total_deposits, max_deposit_per_user)
+    }
+
+    pub fn set_withdrawal_cooldown(ctx: Context<VaultAdmin>, cooldown: i64) -> Result<()> {
+        check_role(
+            &ctx.accounts.vault,
+            ctx.accounts.vault_roles.as_deref(),
+            Role::Admin,
+            &ctx.accounts.vault_authority,
+            ctx.remaining_accounts,
+        )?;
+        ctx.accounts.set_withdrawal_cooldown(cooldown)
+    }
+
+    pub fn set_fees(
+        ctx: Context<VaultAdmin>,
+        deposit_fee_bps: u16,
+        withdraw_fee_bps: u16,
+        performance_fee_bps: u16,
+    ) -> Result<()> {
+        check_role(
+            &ctx.accounts.vault,
+            ctx.accounts.vault_roles.as_deref(),
+            Role::Admin,
+            &ctx.accounts.vault_authority,
+            ctx.remaining_accounts,
+        )?;
+        ctx.accounts.set_fees(deposit_fee_bps, withdraw_fee_bps, performance_fee_bps)
+    }
+
+    pub fn apply_fees(ctx: Context<VaultAdmin>) -> Result<()> {
+        check_role(
+            &ctx.accounts.vault,
+            ctx.accounts.vault_roles.as_deref(),
+            Role::Admin,
+            &ctx.accounts.vault_authority,
+            ctx.remaining_accounts,
+        )?;
+        ctx.accounts.apply_fees()
+    }
+
+    pub fn set_fee_recipient(ctx: Context<VaultAdmin>, fee_recipient: Pubkey) -> Result<()> {
+        check_role(
+            &ctx.accounts.vault,
+            ctx.accounts.vault_roles.as_deref(),
+            Role::Admin,
+            &ctx.accounts.vault_authority,
+            ctx.remaining_accounts,
+        )?;
+        ctx.accounts.set_fee_recipient(fee_recipient)
+    }
+
+    pub fn set_surplus_policy(ctx: Context<VaultAdmin>, surplus_policy: SurplusPolicy) -> Result<()> {
+        check_role(
+            &ctx.accounts.vault,
+            ctx.accounts.vault_roles.as_deref(),
+            Role::Admin,
+            &ctx.accounts.vault_authority,
+            ctx.remaining_accounts,
+        )?;
+        ctx.accounts.set_surplus_policy(surplus_policy)
+    }
+
+    pub fn emergency_shutdown(ctx: Context<VaultAdmin>) -> Result<()> {
+        check_role(
+            &ctx.accounts.vault,
+            ctx.accounts.vault_roles.as_deref(),
+            Role::Admin,
+            &ctx.accounts.vault_authority,
+            ctx.remaining_accounts,
+        )?;
+        ctx.accounts.emergency_shutdown()
+    }
+
+    pub fn pause_vault(ctx: Context<PauseVault>, deposits: bool, withdrawals: bool) -> Result<()> {
+        check_role(
+            &ctx.accounts.vault,
+            ctx.accounts.vault_roles.as_deref(),
+            Role::Pauser,
+            &ctx.accounts.pauser,
+            ctx.remaining_accounts,
+        )?;
+        ctx.accounts.pause_vault(deposits, withdrawals)
+    }
+
+    pub fn unpause_vault(ctx: Context<PauseVault>, deposits: bool, withdrawals: bool) -> Result<()> {
+        check_role(
+            &ctx.accounts.vault,
+            ctx.accounts.vault_roles.as_deref(),
+            Role::Pauser,
+            &ctx.accounts.pauser,
+            ctx.remaining_accounts,
+        )?;
+        ctx.accounts.unpause_vault(deposits, withdrawals)
     }
 }
@@ -1,3 +1,13 @@
 mod vault;
+mod rate_history;
+mod pending_rate_change;
+mod withdrawal_request;
+mod multisig;
+mod vault_roles;
 
-pub use vault::*;
\ No newline at end of file
+pub use vault::*;
+pub use rate_history::*;
+pub use pending_rate_change::*;
+pub use withdrawal_request::*;
+pub use multisig::*;
+pub use vault_roles::*;
@@ -0,0 +1,28 @@
+use anchor_lang::prelude::*;
+
+/// Largest signer set a multisig can have.
+pub const MAX_MULTISIG_SIGNERS: usize = 11;
+
+/// M-of-N signer set that can be used as a vault authority.
+#[account]
+#[derive(InitSpace)]
+pub struct Multisig {
+    #[max_len(MAX_MULTISIG_SIGNERS)]
+    pub signers: Vec<Pubkey>,
+    pub threshold: u8,
+    pub create_key: Pubkey,
+    pub bump: u8,
+}
+
+impl Multisig {
+    /// Number of distinct members of the signer set among `signed`.
+    pub fn approvals(&self, signed: &[Pubkey]) -> usize {
+        let mut approved: Vec<&Pubkey> = Vec::with_capacity(self.signers.len());
+        for key in signed {
+            if self.signers.contains(key) && !approved.contains(&key) {
+                approved.push(key);
+            }
+        }
+        approved.len()
+    }
+}
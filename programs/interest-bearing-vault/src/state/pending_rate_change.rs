@@ -0,0 +1,15 @@
+use anchor_lang::prelude::*;
+
+/// Rate change delay of new vaults, in seconds.
+pub const DEFAULT_RATE_CHANGE_DELAY: i64 = 86_400;
+
+#[account]
+#[derive(InitSpace)]
+pub struct PendingRateChange {
+    pub vault: Pubkey,
+    pub proposer: Pubkey,
+    pub new_rate: i16,
+    pub proposed_at: i64,
+    pub executable_at: i64,
+    pub bump: u8,
+}
@@ -0,0 +1,37 @@
+use anchor_lang::prelude::*;
+
+/// Number of rate changes kept on chain, older entries are dropped first.
+pub const MAX_RATE_HISTORY: usize = 32;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct RateChange {
+    pub old_rate: i16,
+    pub new_rate: i16,
+    pub timestamp: i64,
+    pub signer: Pubkey,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct RateHistory {
+    pub vault: Pubkey,
+    pub total_changes: u64,
+    #[max_len(MAX_RATE_HISTORY)]
+    pub changes: Vec<RateChange>,
+    pub bump: u8,
+}
+
+impl RateHistory {
+    /// Appends `change`, dropping the oldest entry once the history is full.
+    pub fn record(&mut self, change: RateChange) -> Result<()> {
+        if self.changes.len() >= MAX_RATE_HISTORY {
+            self.changes.remove(0);
+        }
+        self.changes.push(change);
+        self.total_changes = self.total_changes
+            .checked_add(1)
+            .ok_or(crate::errors::VaultError::Overflow)?;
+
+        Ok(())
+    }
+}
@@ -1,16 +1,303 @@
 use anchor_lang::prelude::*;
 
+use crate::utils::{convert_to_assets, convert_to_shares, fee_amount, interest_inclusive_amount, Rounding};
+
 pub const VAULT_SEED: &str = "vault";
 
+/// Longest lock a deposit can put on a position, four years
+pub const MAX_LOCK_DURATION: i64 = 4 * 365 * 86_400;
+
+/// What happens to reserve tokens that did not arrive through a deposit.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum SurplusPolicy {
+    /// Shared pro rata by share holders
+    Yield,
+    /// Held apart until the vault authority recovers it
+    Recoverable,
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct Vault {
+    /// Authority that created the vault, part of the vault PDA seeds and never changes
+    pub creator: Pubkey,
     pub vault_authority: Pubkey,
+    /// Authority proposed by `propose_authority`, `Pubkey::default()` if none
+    pub pending_authority: Pubkey,
     pub mint : Pubkey,
+    pub vault_index: u64,
     pub token_reserve: Pubkey,
+    pub share_mint: Pubkey,
     pub token_reserve_amount: u64,
+    /// Positions currently holding shares
     pub num_depositors: u64,
+    /// Deposits made into the vault over its lifetime
+    pub num_deposits: u64,
+    /// Withdrawals, redemptions and withdrawal requests over the vault's lifetime
+    pub num_withdrawals: u64,
+    /// Registry entries that have not been closed yet
+    pub open_positions: u64,
+    /// Up to when the reserve's interest has been checkpointed into `unrealized_interest`
+    pub last_interest_realization: i64,
+    /// Interest earned by `token_reserve_amount` but not yet minted by `realize_interest`
+    pub unrealized_interest: u64,
+    pub min_rate: i16,
+    pub max_rate: i16,
+    pub rate_change_delay: i64,
+    /// Lower delay set by `set_rate_change_delay`, in force from `pending_rate_change_delay_at`
+    pub pending_rate_change_delay: i64,
+    /// When `pending_rate_change_delay` takes over, 0 if no decrease is pending
+    pub pending_rate_change_delay_at: i64,
+    pub deposits_paused: bool,
+    pub withdrawals_paused: bool,
+    /// Set for good by `emergency_shutdown`, depositors can then only exit pro rata
+    pub emergency_shutdown: bool,
+    pub max_total_deposits: u64,
+    pub max_deposit_per_user: u64,
+    pub withdrawal_cooldown: i64,
+    pub next_withdrawal_request_id: u64,
+    /// Id of the oldest withdrawal request not fulfilled yet
+    pub next_withdrawal_to_fulfil: u64,
+    /// Assets owed to queued withdrawal requests, not part of `token_reserve_amount`
+    pub queued_withdrawals: u64,
+    /// Part of `queued_withdrawals` already set aside in the reserve for claiming
+    pub fulfilled_withdrawals: u64,
+    pub deposit_fee_bps: u16,
+    pub withdraw_fee_bps: u16,
+    pub performance_fee_bps: u16,
+    /// Fees raised by `set_fees`, applied by `apply_fees` from `pending_fees_at`
+    pub pending_deposit_fee_bps: u16,
+    pub pending_withdraw_fee_bps: u16,
+    pub pending_performance_fee_bps: u16,
+    /// When the pending fees can be applied, 0 if no increase is pending
+    pub pending_fees_at: i64,
+    /// Token account collected fees are paid to
+    pub fee_recipient: Pubkey,
+    /// Fees held in the reserve until collected, not part of `token_reserve_amount`
+    pub accrued_fees: u64,
+    pub surplus_policy: SurplusPolicy,
+    /// Surplus set aside under `SurplusPolicy::Recoverable`, not part of `token_reserve_amount`
+    pub excess_reserve: u64,
     pub bump:u8,
+    pub share_mint_bump: u8,
+}
+
+impl Vault {
+    /// Books tokens that reached the reserve outside of a deposit (yield, donations).
+    /// Under `SurplusPolicy::Yield` they join `token_reserve_amount` and are shared pro rata
+    /// by existing share holders, otherwise they are set aside as `excess_reserve`.
+    /// Tokens owed to queued withdrawals, uncollected fees and excess are not surplus.
+    pub fn absorb_reserve_surplus(&mut self, reserve_balance: u64) -> Result<()> {
+        let backing = reserve_balance
+            .saturating_sub(self.queued_withdrawals)
+            .saturating_sub(self.accrued_fees)
+            .saturating_sub(self.excess_reserve);
+        if backing > self.token_reserve_amount {
+            match self.surplus_policy {
+                SurplusPolicy::Yield => self.token_reserve_amount = backing,
+                SurplusPolicy::Recoverable => {
+                    self.excess_reserve = self.excess_reserve
+                        .checked_add(backing - self.token_reserve_amount)
+                        .ok_or(crate::errors::VaultError::Overflow)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rate change delay in force at `now`, a lowered delay only counts once the delay
+    /// it replaces has elapsed.
+    pub fn effective_rate_change_delay(&self, now: i64) -> i64 {
+        if self.pending_rate_change_delay_at != 0 && now >= self.pending_rate_change_delay_at {
+            self.pending_rate_change_delay
+        } else {
+            self.rate_change_delay
+        }
+    }
+
+    /// Assets new shares are priced against: `token_reserve_amount` plus checkpointed
+    /// interest not minted yet, net of the performance fee it will be charged, so a
+    /// deposit cannot buy into interest earned before it arrived.
+    pub fn total_assets_with_unrealized(&self) -> Result<u64> {
+        let interest = self.unrealized_interest
+            .checked_sub(fee_amount(self.unrealized_interest, self.performance_fee_bps)?)
+            .ok_or(crate::errors::VaultError::Underflow)?;
+
+        self.token_reserve_amount
+            .checked_add(interest)
+            .ok_or(error!(crate::errors::VaultError::Overflow))
+    }
+
+    /// What an emergency exit owed `amount` is paid. When the reserve no longer backs what
+    /// share holders, queued withdrawals and the fee recipient are owed, every exit and the
+    /// fee collection take the same haircut.
+    pub fn emergency_payout(&self, amount: u64, reserve_balance: u64) -> Result<u64> {
+        let backing = reserve_balance.saturating_sub(self.excess_reserve);
+        let owed = self.token_reserve_amount
+            .checked_add(self.queued_withdrawals)
+            .and_then(|total| total.checked_add(self.accrued_fees))
+            .ok_or(crate::errors::VaultError::Overflow)?;
+        if backing >= owed {
+            return Ok(amount);
+        }
+
+        let payout = (amount as u128)
+            .checked_mul(backing as u128)
+            .ok_or(crate::errors::VaultError::Overflow)?
+            / owed as u128;
+
+        Ok(u64::try_from(payout).map_err(|_| crate::errors::VaultError::Overflow)?)
+    }
+
+    /// Reserve balance the vault's books account for.
+    pub fn booked_reserve(&self) -> Result<u64> {
+        self.token_reserve_amount
+            .checked_add(self.queued_withdrawals)
+            .and_then(|total| total.checked_add(self.accrued_fees))
+            .and_then(|total| total.checked_add(self.excess_reserve))
+            .ok_or(error!(crate::errors::VaultError::Overflow))
+    }
+
+    /// Reserve tokens that may leave the vault, excluding those set aside for fulfilled
+    /// withdrawal requests, uncollected fees and recoverable excess.
+    pub fn available_liquidity(&self, reserve_balance: u64) -> u64 {
+        reserve_balance
+            .saturating_sub(self.fulfilled_withdrawals)
+            .saturating_sub(self.accrued_fees)
+            .saturating_sub(self.excess_reserve)
+    }
+
+    /// Books `fee` as owed to the fee recipient.
+    pub fn accrue_fee(&mut self, fee: u64) -> Result<()> {
+        self.accrued_fees = self.accrued_fees
+            .checked_add(fee)
+            .ok_or(crate::errors::VaultError::Overflow)?;
+
+        Ok(())
+    }
+
+    /// Counts a deposit into a position that held `shares_before`, a position
+    /// becomes an active depositor when it first holds shares.
+    pub fn record_deposit(&mut self, shares_before: u64) -> Result<()> {
+        self.num_deposits = self.num_deposits
+            .checked_add(1)
+            .ok_or(crate::errors::VaultError::Overflow)?;
+
+        if shares_before == 0 {
+            self.num_depositors = self.num_depositors
+                .checked_add(1)
+                .ok_or(crate::errors::VaultError::Overflow)?;
+        }
+
+        Ok(())
+    }
+
+    /// Counts a withdrawal that left the position with `shares_after`, a position
+    /// stops being an active depositor once it holds no shares.
+    pub fn record_withdrawal(&mut self, shares_after: u64) -> Result<()> {
+        self.num_withdrawals = self.num_withdrawals
+            .checked_add(1)
+            .ok_or(crate::errors::VaultError::Overflow)?;
+
+        if shares_after == 0 {
+            self.num_depositors = self.num_depositors
+                .checked_sub(1)
+                .ok_or(crate::errors::VaultError::Underflow)?;
+        }
+
+        Ok(())
+    }
+
+    /// Credits `amount` tokens that reached the reserve to `entry`, `share_supply` being the
+    /// share supply before the deposit. Takes the deposit fee, enforces the deposit caps,
+    /// prices the new shares and snapshots the position's interest around the deposit.
+    /// Interest must be checkpointed and surplus absorbed beforehand; the caller mints the
+    /// shares. Returns the fee and the number of shares.
+    pub fn credit_deposit(
+        &mut self,
+        entry: &mut VaultRegistryEntry,
+        amount: u64,
+        share_supply: u64,
+        mint: &AccountInfo,
+        now: i64,
+    ) -> Result<(u64, u64)> {
+        // Only the amount net of the deposit fee backs the new shares
+        let fee = fee_amount(amount, self.deposit_fee_bps)?;
+        let net_amount = amount
+            .checked_sub(fee)
+            .ok_or(crate::errors::VaultError::Underflow)?;
+        self.check_deposit_caps(net_amount, entry.token_balance)?;
+        let shares = convert_to_shares(
+            net_amount,
+            self.total_assets_with_unrealized()?,
+            share_supply,
+            Rounding::Down,
+        )?;
+        require!(shares > 0, crate::errors::VaultError::ZeroShares);
+
+        // Record interest earned on the existing position before it grows
+        let position_value = convert_to_assets(entry.shares, self.token_reserve_amount, share_supply, Rounding::Down)?;
+        entry.accrue_interest(interest_inclusive_amount(mint, position_value, now)?, now)?;
+
+        if entry.num_deposits == 0 {
+            self.open_positions = self.open_positions
+                .checked_add(1)
+                .ok_or(crate::errors::VaultError::Overflow)?;
+        }
+        self.token_reserve_amount = self.token_reserve_amount
+            .checked_add(net_amount)
+            .ok_or(crate::errors::VaultError::Overflow)?;
+        self.accrue_fee(fee)?;
+        self.record_deposit(entry.shares)?;
+
+        entry.shares = entry.shares
+            .checked_add(shares)
+            .ok_or(crate::errors::VaultError::Overflow)?;
+        entry.token_balance = entry.token_balance
+            .checked_add(net_amount)
+            .ok_or(crate::errors::VaultError::Overflow)?;
+        entry.num_deposits = entry.num_deposits
+            .checked_add(1)
+            .ok_or(crate::errors::VaultError::Overflow)?;
+        let position_value = convert_to_assets(
+            entry.shares,
+            self.token_reserve_amount,
+            share_supply
+                .checked_add(shares)
+                .ok_or(crate::errors::VaultError::Overflow)?,
+            Rounding::Down,
+        )?;
+        entry.interest_inclusive_balance = interest_inclusive_amount(mint, position_value, now)?;
+
+        Ok((fee, shares))
+    }
+
+    /// Fails if depositing `amount` would take the vault or the depositor's principal
+    /// (`user_balance`) over its cap. A cap of 0 means unlimited.
+    pub fn check_deposit_caps(&self, amount: u64, user_balance: u64) -> Result<()> {
+        if self.max_total_deposits != 0 {
+            let total = self.token_reserve_amount
+                .checked_add(amount)
+                .ok_or(crate::errors::VaultError::Overflow)?;
+            require!(
+                total <= self.max_total_deposits,
+                crate::errors::VaultError::VaultDepositCapExceeded
+            );
+        }
+
+        if self.max_deposit_per_user != 0 {
+            let total = user_balance
+                .checked_add(amount)
+                .ok_or(crate::errors::VaultError::Overflow)?;
+            require!(
+                total <= self.max_deposit_per_user,
+                crate::errors::VaultError::UserDepositCapExceeded
+            );
+        }
+
+        Ok(())
+    }
 }
 
 #[account]
@@ -21,7 +308,28 @@ pub struct VaultRegistryEntry {
     pub vault: Pubkey,
     pub mint: Pubkey,
     pub token_balance: u64,
+    pub shares: u64,
+    pub interest_inclusive_balance: u64,
+    pub accrued_interest: i64,
+    pub last_accrual_timestamp: i64,
+    pub locked_until: i64,
     pub num_withdraws: u64,
     pub num_deposits: u64,
     pub bump:u8,
+}
+
+impl VaultRegistryEntry {
+    /// Adds the interest earned since the last accrual to `accrued_interest`.
+    /// `interest_inclusive_balance` is the position's interest-inclusive value right now,
+    /// before the caller changes the position and takes a new snapshot.
+    pub fn accrue_interest(&mut self, interest_inclusive_balance: u64, now: i64) -> Result<()> {
+        if self.last_accrual_timestamp != 0 {
+            let earned = interest_inclusive_balance as i128 - self.interest_inclusive_balance as i128;
+            self.accrued_interest = i64::try_from(self.accrued_interest as i128 + earned)
+                .map_err(|_| crate::errors::VaultError::Overflow)?;
+        }
+        self.last_accrual_timestamp = now;
+
+        Ok(())
+    }
 }
\ No newline at end of file
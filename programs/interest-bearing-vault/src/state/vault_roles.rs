@@ -0,0 +1,66 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::VaultError;
+
+/// Number of role assignments a vault can hold.
+pub const MAX_ROLE_MEMBERS: usize = 16;
+
+/// Privileges that can be delegated by the vault authority, which holds all of them.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum Role {
+    /// Vault settings and closing the vault, moving funds out of the reserve stays with the authority
+    Admin,
+    /// Fulfilling queued withdrawals
+    Operator,
+    /// Pausing and unpausing deposits and withdrawals
+    Pauser,
+    /// Proposing, executing and cancelling rate changes
+    RateSetter,
+    /// Collecting accrued fees
+    FeeCollector,
+}
+
+impl Role {
+    /// `VaultError::Unauthorized`, with a message naming this role.
+    pub fn unauthorized_error(&self) -> Error {
+        let role = match self {
+            Role::Admin => "admin",
+            Role::Operator => "operator",
+            Role::Pauser => "pauser",
+            Role::RateSetter => "rate setter",
+            Role::FeeCollector => "fee collector",
+        };
+
+        AnchorError {
+            error_name: VaultError::Unauthorized.name(),
+            error_code_number: VaultError::Unauthorized.into(),
+            error_msg: format!("Unauthorized: signer lacks the {} role", role),
+            error_origin: None,
+            compared_values: None,
+        }
+        .into()
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct RoleAssignment {
+    pub member: Pubkey,
+    pub role: Role,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct VaultRoles {
+    pub vault: Pubkey,
+    #[max_len(MAX_ROLE_MEMBERS)]
+    pub assignments: Vec<RoleAssignment>,
+    pub bump: u8,
+}
+
+impl VaultRoles {
+    pub fn has_role(&self, member: &Pubkey, role: Role) -> bool {
+        self.assignments
+            .iter()
+            .any(|a| a.member == *member && a.role == role)
+    }
+}
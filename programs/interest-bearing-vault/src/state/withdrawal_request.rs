@@ -0,0 +1,15 @@
+use anchor_lang::prelude::*;
+
+/// A queued withdrawal, one account per request so the queue has no size limit.
+/// Requests are fulfilled in `id` order and closed when claimed.
+#[account]
+#[derive(InitSpace)]
+pub struct WithdrawalRequest {
+    pub vault: Pubkey,
+    pub id: u64,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub requested_at: i64,
+    pub fulfilled: bool,
+    pub bump: u8,
+}
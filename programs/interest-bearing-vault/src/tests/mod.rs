@@ -27,7 +27,7 @@ mod tests {
                 extension::{
                     interest_bearing_mint::InterestBearingConfig,
                     transfer_hook::TransferHook as TransferHookExt, BaseStateWithExtensions,
-                    ExtensionType, StateWithExtensions,
+                    ExtensionType, StateWithExtensions, StateWithExtensionsMut,
                 },
                 state::{Mint as Token2022Mint,},
                 ID as TOKEN_PROGRAM_ID,
@@ -58,6 +58,15 @@ mod tests {
 
     static PROGRAM_ID: Pubkey = crate::ID;
 
+    // PDAs signing the self-CPI that carries emitted events
+    fn event_authority() -> Pubkey {
+        Pubkey::find_program_address(&[b"__event_authority"], &PROGRAM_ID).0
+    }
+
+    fn hook_event_authority() -> Pubkey {
+        Pubkey::find_program_address(&[b"__event_authority"], &transfer_hook::ID).0
+    }
+
     // Helper function to create associated token account
     fn create_ata(
         program: &mut LiteSVM,
@@ -127,6 +136,48 @@ mod tests {
             .expect("Failed to mint tokens");
     }
 
+    // Helper function to add an address to the transfer hook whitelist for a mint
+    fn add_to_whitelist(
+        program: &mut LiteSVM,
+        admin: &Keypair,
+        mint: &Pubkey,
+        address: &Pubkey,
+    ) -> Pubkey {
+        let (whitelist_pda, _) = Pubkey::find_program_address(
+            &[b"whitelist", mint.as_ref(), address.as_ref()],
+            &transfer_hook::ID,
+        );
+
+        let accounts = transfer_hook::accounts::WhitelistOperations {
+            admin: admin.pubkey(),
+            address: *address,
+            mint: *mint,
+            whitelist_PDA: whitelist_pda,
+            system_program: SYSTEM_PROGRAM_ID,
+            event_authority: hook_event_authority(),
+            program: transfer_hook::ID,
+        };
+
+        let ix = Instruction {
+            program_id: transfer_hook::ID,
+            accounts: accounts.to_account_metas(None),
+            data: transfer_hook::instruction::AddToWhitelist {}.data(),
+        };
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&admin.pubkey()),
+            &[admin],
+            program.latest_blockhash(),
+        );
+
+        program
+            .send_transaction(transaction)
+            .expect("Failed to add address to whitelist");
+
+        whitelist_pda
+    }
+
     fn initialize_extra_account_metas(
         program: &mut LiteSVM,
         payer: &Keypair,
@@ -240,6 +291,374 @@ mod tests {
         (program, payer)
     }
 
+    // Accounts of a vault created by `setup_vault`
+    struct VaultFixture {
+        mint: Keypair,
+        vault: Pubkey,
+        reserve: Pubkey,
+        share_mint: Pubkey,
+        extra_account_meta_list: Pubkey,
+        vault_whitelist: Pubkey,
+    }
+
+    // Helper function to build an `initialize_vault` instruction,
+    // returns it with the vault, share mint and reserve addresses
+    fn initialize_vault_ix(
+        authority: &Pubkey,
+        mint: &Pubkey,
+        vault_index: u64,
+    ) -> (Instruction, Pubkey, Pubkey, Pubkey) {
+        let (vault, _) = Pubkey::find_program_address(
+            &[b"vault", authority.as_ref(), mint.as_ref(), &vault_index.to_le_bytes()],
+            &PROGRAM_ID,
+        );
+
+        let (share_mint, _) =
+            Pubkey::find_program_address(&[b"share_mint", vault.as_ref()], &PROGRAM_ID);
+
+        let reserve = associated_token::get_associated_token_address_with_program_id(
+            &vault,
+            mint,
+            &TOKEN_PROGRAM_ID,
+        );
+
+        let ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::InitializeVault {
+                vault_authority: *authority,
+                mint: *mint,
+                vault,
+                share_mint,
+                token_reserve: reserve,
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                share_token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+                event_authority: event_authority(),
+                program: PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::InitializeVault { vault_index }.data(),
+        };
+
+        (ix, vault, share_mint, reserve)
+    }
+
+    // Helper function to create an interest bearing mint and a vault for it,
+    // with the transfer hook set up and the vault whitelisted
+    fn setup_vault(program: &mut LiteSVM, payer: &Keypair, interest_rate: i16) -> VaultFixture {
+        let mint = Keypair::new();
+
+        let (extra_account_meta_list, _) = Pubkey::find_program_address(
+            &[b"extra-account-metas", mint.pubkey().as_ref()],
+            &transfer_hook::ID,
+        );
+
+        let init_mint_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::TokenFactory {
+                user: payer.pubkey(),
+                mint: mint.pubkey(),
+                extra_account_meta_list,
+                hook_program_id: transfer_hook::ID,
+                system_program: SYSTEM_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                event_authority: event_authority(),
+                program: PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::CreateMintWithExtensions { interest_rate }.data(),
+        };
+
+        let (init_vault_ix, vault, share_mint, reserve) =
+            initialize_vault_ix(&payer.pubkey(), &mint.pubkey(), 0);
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[init_mint_ix, init_vault_ix],
+            Some(&payer.pubkey()),
+            &[payer, &mint],
+            program.latest_blockhash(),
+        );
+
+        program
+            .send_transaction(transaction)
+            .expect("Failed to create mint and vault");
+
+        initialize_extra_account_metas(program, payer, &mint.pubkey());
+        let vault_whitelist = add_to_whitelist(program, payer, &mint.pubkey(), &vault);
+
+        VaultFixture {
+            mint,
+            vault,
+            reserve,
+            share_mint,
+            extra_account_meta_list,
+            vault_whitelist,
+        }
+    }
+
+    // Helper function to whitelist a user and fund their token account from the mint authority
+    fn fund_user(
+        program: &mut LiteSVM,
+        payer: &Keypair,
+        fixture: &VaultFixture,
+        user: &Pubkey,
+        amount: u64,
+    ) -> Pubkey {
+        add_to_whitelist(program, payer, &fixture.mint.pubkey(), user);
+        let user_ata = create_ata(program, payer, user, &fixture.mint.pubkey());
+        mint_tokens_to(program, &fixture.mint.pubkey(), &user_ata, payer, amount);
+        user_ata
+    }
+
+    fn registry_address(fixture: &VaultFixture, user: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"vault_registry", fixture.vault.as_ref(), user.as_ref()],
+            &PROGRAM_ID,
+        )
+        .0
+    }
+
+    fn share_account_address(fixture: &VaultFixture, user: &Pubkey) -> Pubkey {
+        associated_token::get_associated_token_address_with_program_id(
+            user,
+            &fixture.share_mint,
+            &TOKEN_PROGRAM_ID,
+        )
+    }
+
+    fn deposit_ix(fixture: &VaultFixture, depositor: &Pubkey, amount: u64) -> Instruction {
+        locked_deposit_ix(fixture, depositor, amount, 0)
+    }
+
+    fn locked_deposit_ix(
+        fixture: &VaultFixture,
+        depositor: &Pubkey,
+        amount: u64,
+        lock_duration: i64,
+    ) -> Instruction {
+        let mint = fixture.mint.pubkey();
+        let (depositor_whitelist, _) = Pubkey::find_program_address(
+            &[b"whitelist", mint.as_ref(), depositor.as_ref()],
+            &transfer_hook::ID,
+        );
+
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Deposit {
+                depositor: *depositor,
+                vault: fixture.vault,
+                vault_registry_entry: registry_address(fixture, depositor),
+                mint,
+                depositor_token_account: associated_token::get_associated_token_address_with_program_id(
+                    depositor,
+                    &mint,
+                    &TOKEN_PROGRAM_ID,
+                ),
+                vault_token_reserve: fixture.reserve,
+                share_mint: fixture.share_mint,
+                depositor_share_account: share_account_address(fixture, depositor),
+                transfer_hook_program: Some(transfer_hook::ID),
+                extra_account_meta_list: Some(fixture.extra_account_meta_list),
+                depositor_whitelist_PDA: Some(depositor_whitelist),
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                share_token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+                event_authority: event_authority(),
+                program: PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::Deposit { amount, lock_duration, max_fee: 0 }.data(),
+        }
+    }
+
+    fn withdraw_accounts(fixture: &VaultFixture, withdrawer: &Pubkey) -> Vec<anchor_lang::prelude::AccountMeta> {
+        let mint = fixture.mint.pubkey();
+        crate::accounts::Withdraw {
+            withdrawer: *withdrawer,
+            vault: fixture.vault,
+            vault_registry_entry: registry_address(fixture, withdrawer),
+            mint,
+            recipient_token_account: associated_token::get_associated_token_address_with_program_id(
+                withdrawer,
+                &mint,
+                &TOKEN_PROGRAM_ID,
+            ),
+            recipient_whitelist: Some(Pubkey::find_program_address(
+                &[b"whitelist", mint.as_ref(), withdrawer.as_ref()],
+                &transfer_hook::ID,
+            )
+            .0),
+            vault_token_reserve: fixture.reserve,
+            share_mint: fixture.share_mint,
+            withdrawer_share_account: share_account_address(fixture, withdrawer),
+            extra_account_meta_list: Some(fixture.extra_account_meta_list),
+            transfer_hook_program: Some(transfer_hook::ID),
+            vault_whitelist: Some(fixture.vault_whitelist),
+            associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+            token_program: TOKEN_PROGRAM_ID,
+            share_token_program: TOKEN_PROGRAM_ID,
+            system_program: SYSTEM_PROGRAM_ID,
+            event_authority: event_authority(),
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None)
+    }
+
+    // Helper function to create a mint without a transfer hook, optionally charging
+    // `transfer_fee_bps`, and a vault for it
+    fn setup_hookless_vault(program: &mut LiteSVM, payer: &Keypair, transfer_fee_bps: Option<u16>) -> VaultFixture {
+        use anchor_lang::solana_program::system_instruction::create_account;
+        use anchor_spl::token_2022::spl_token_2022::{
+            extension::transfer_fee::instruction::initialize_transfer_fee_config,
+            instruction::initialize_mint2,
+        };
+
+        let mint = Keypair::new();
+        let extensions: &[ExtensionType] = match transfer_fee_bps {
+            Some(_) => &[ExtensionType::TransferFeeConfig],
+            None => &[],
+        };
+        let space = ExtensionType::try_calculate_account_len::<Token2022Mint>(extensions).unwrap();
+
+        let mut ixs = vec![create_account(
+            &payer.pubkey(),
+            &mint.pubkey(),
+            program.minimum_balance_for_rent_exemption(space),
+            space as u64,
+            &TOKEN_PROGRAM_ID,
+        )];
+        if let Some(bps) = transfer_fee_bps {
+            ixs.push(
+                initialize_transfer_fee_config(
+                    &TOKEN_PROGRAM_ID,
+                    &mint.pubkey(),
+                    Some(&payer.pubkey()),
+                    Some(&payer.pubkey()),
+                    bps,
+                    u64::MAX,
+                )
+                .unwrap(),
+            );
+        }
+        ixs.push(initialize_mint2(&TOKEN_PROGRAM_ID, &mint.pubkey(), &payer.pubkey(), None, 9).unwrap());
+
+        let (init_vault_ix, vault, share_mint, reserve) = initialize_vault_ix(&payer.pubkey(), &mint.pubkey(), 0);
+        ixs.push(init_vault_ix);
+        send(program, &ixs, payer, &[payer, &mint]).expect("Failed to create mint and vault");
+
+        VaultFixture {
+            mint,
+            vault,
+            reserve,
+            share_mint,
+            extra_account_meta_list: Pubkey::default(),
+            vault_whitelist: Pubkey::default(),
+        }
+    }
+
+    // Deposit into a vault set up by `setup_hookless_vault`, no hook accounts are passed
+    fn hookless_deposit_ix(fixture: &VaultFixture, depositor: &Pubkey, amount: u64, max_fee: u64) -> Instruction {
+        let mint = fixture.mint.pubkey();
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Deposit {
+                depositor: *depositor,
+                vault: fixture.vault,
+                vault_registry_entry: registry_address(fixture, depositor),
+                mint,
+                depositor_token_account: associated_token::get_associated_token_address_with_program_id(
+                    depositor,
+                    &mint,
+                    &TOKEN_PROGRAM_ID,
+                ),
+                vault_token_reserve: fixture.reserve,
+                share_mint: fixture.share_mint,
+                depositor_share_account: share_account_address(fixture, depositor),
+                transfer_hook_program: None,
+                extra_account_meta_list: None,
+                depositor_whitelist_PDA: None,
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                share_token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+                event_authority: event_authority(),
+                program: PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::Deposit { amount, lock_duration: 0, max_fee }.data(),
+        }
+    }
+
+    // Withdraw from a vault set up by `setup_hookless_vault`, no hook accounts are passed
+    fn hookless_withdraw_ix(fixture: &VaultFixture, withdrawer: &Pubkey, amount: u64, max_fee: u64) -> Instruction {
+        let mint = fixture.mint.pubkey();
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Withdraw {
+                withdrawer: *withdrawer,
+                vault: fixture.vault,
+                vault_registry_entry: registry_address(fixture, withdrawer),
+                mint,
+                recipient_token_account: associated_token::get_associated_token_address_with_program_id(
+                    withdrawer,
+                    &mint,
+                    &TOKEN_PROGRAM_ID,
+                ),
+                recipient_whitelist: None,
+                vault_token_reserve: fixture.reserve,
+                share_mint: fixture.share_mint,
+                withdrawer_share_account: share_account_address(fixture, withdrawer),
+                extra_account_meta_list: None,
+                transfer_hook_program: None,
+                vault_whitelist: None,
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                share_token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+                event_authority: event_authority(),
+                program: PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::Withdraw { amount, max_fee }.data(),
+        }
+    }
+
+    // Helper function to send a transaction, failures carry the program logs
+    fn send(program: &mut LiteSVM, ixs: &[Instruction], payer: &Keypair, signers: &[&Keypair]) -> Result<(), String> {
+        let transaction = Transaction::new_signed_with_payer(
+            ixs,
+            Some(&payer.pubkey()),
+            signers,
+            program.latest_blockhash(),
+        );
+        program
+            .send_transaction(transaction)
+            .map(|_| ())
+            .map_err(|e| format!("{:?}\n{:#?}", e.err, e.meta.logs))
+    }
+
+    fn get_vault(program: &LiteSVM, vault: &Pubkey) -> crate::state::Vault {
+        let account = program.get_account(vault).expect("Vault should exist");
+        let mut data: &[u8] = &account.data;
+        anchor_lang::AccountDeserialize::try_deserialize(&mut data).expect("Failed to deserialize vault")
+    }
+
+    fn get_registry(program: &LiteSVM, registry: &Pubkey) -> crate::state::VaultRegistryEntry {
+        let account = program.get_account(registry).expect("Registry entry should exist");
+        let mut data: &[u8] = &account.data;
+        anchor_lang::AccountDeserialize::try_deserialize(&mut data).expect("Failed to deserialize registry")
+    }
+
+    fn token_balance(program: &LiteSVM, token_account: &Pubkey) -> u64 {
+        let account = program.get_account(token_account).expect("Token account should exist");
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+            .expect("Failed to unpack token account")
+            .base
+            .amount
+    }
+
     #[test]
     fn test_create_interest_bearing_mint() {
         // Setup the test environment by initializing LiteSVM and creating a payer keypair
@@ -272,6 +691,8 @@ mod tests {
             hook_program_id: transfer_hook::ID,
             system_program: SYSTEM_PROGRAM_ID,
             token_program: TOKEN_PROGRAM_ID,
+            event_authority: event_authority(),
+            program: PROGRAM_ID,
         };
 
         let instruction_data = crate::instruction::CreateMintWithExtensions { interest_rate };
@@ -402,6 +823,8 @@ mod tests {
             hook_program_id: transfer_hook::ID,
             system_program: SYSTEM_PROGRAM_ID,
             token_program: TOKEN_PROGRAM_ID,
+            event_authority: event_authority(),
+            program: PROGRAM_ID,
         };
 
         let instruction_data = crate::instruction::CreateMintWithExtensions { interest_rate };
@@ -501,7 +924,13 @@ mod tests {
         // Init vault process start
 
         let (vault_pda, _bump) =
-            Pubkey::find_program_address(&[b"vault", payer_pubkey.as_ref()], &PROGRAM_ID);
+            Pubkey::find_program_address(
+                &[b"vault", payer_pubkey.as_ref(), mint.pubkey().as_ref(), &0u64.to_le_bytes()],
+                &PROGRAM_ID,
+            );
+
+        let (share_mint, _) =
+            Pubkey::find_program_address(&[b"share_mint", vault_pda.as_ref()], &PROGRAM_ID);
         msg!("test_init_vault: vault PDA: {}\n", vault_pda);
 
         // Create the vault's associated token account for Mint
@@ -519,12 +948,15 @@ mod tests {
         let accounts = crate::accounts::InitializeVault {
             vault_authority: payer_pubkey,
             mint: mint.pubkey(),
-            hook_program_id: transfer_hook_program_id,
             vault: vault_pda,
+            share_mint,
             token_reserve: reserve_ata,
             associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
             token_program: TOKEN_PROGRAM_ID,
+            share_token_program: TOKEN_PROGRAM_ID,
             system_program: SYSTEM_PROGRAM_ID,
+            event_authority: event_authority(),
+            program: PROGRAM_ID,
         };
 
         // let instruction_data = crate::instruction::InitializeVault {};
@@ -532,7 +964,7 @@ mod tests {
         let init_vault_ix = Instruction {
             program_id: PROGRAM_ID,
             accounts: accounts.to_account_metas(None),
-            data: crate::instruction::InitializeVault {}.data(),
+            data: crate::instruction::InitializeVault { vault_index: 0 }.data(),
         };
 
         // Create and send transaction
@@ -589,7 +1021,9 @@ mod tests {
         // Verify vault properties
         assert_eq!(vault.vault_authority, payer_pubkey, "Vault authority should be payer");
         assert_eq!(vault.mint, mint.pubkey(), "Vault mint should match");
+        assert_eq!(vault.vault_index, 0, "Vault index should match");
         assert_eq!(vault.token_reserve, reserve_ata, "Vault token reserve should match");
+        assert_eq!(vault.share_mint, share_mint, "Vault share mint should match");
         assert_eq!(vault.token_reserve_amount, 0, "Initial reserve amount should be 0");
         assert_eq!(vault.num_depositors, 0, "Initial depositors should be 0");
 
@@ -1109,6 +1543,8 @@ mod tests {
             hook_program_id: transfer_hook::ID,
             system_program: SYSTEM_PROGRAM_ID,
             token_program: TOKEN_PROGRAM_ID,
+            event_authority: event_authority(),
+            program: PROGRAM_ID,
         };
 
         let init_mint_ix = Instruction {
@@ -1128,7 +1564,13 @@ mod tests {
 
         // Initialize vault
         let (vault_pda, _) =
-            Pubkey::find_program_address(&[b"vault", payer_pubkey.as_ref()], &PROGRAM_ID);
+            Pubkey::find_program_address(
+                &[b"vault", payer_pubkey.as_ref(), mint.pubkey().as_ref(), &0u64.to_le_bytes()],
+                &PROGRAM_ID,
+            );
+
+        let (share_mint, _) =
+            Pubkey::find_program_address(&[b"share_mint", vault_pda.as_ref()], &PROGRAM_ID);
 
         let reserve_ata = associated_token::get_associated_token_address_with_program_id(
             &vault_pda,
@@ -1143,18 +1585,21 @@ mod tests {
         let accounts = crate::accounts::InitializeVault {
             vault_authority: payer_pubkey,
             mint: mint.pubkey(),
-            hook_program_id: transfer_hook_program_id,
             vault: vault_pda,
+            share_mint,
             token_reserve: reserve_ata,
             associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
             token_program: TOKEN_PROGRAM_ID,
+            share_token_program: TOKEN_PROGRAM_ID,
             system_program: SYSTEM_PROGRAM_ID,
+            event_authority: event_authority(),
+            program: PROGRAM_ID,
         };
 
         let init_vault_ix = Instruction {
             program_id: PROGRAM_ID,
             accounts: accounts.to_account_metas(None),
-            data: crate::instruction::InitializeVault {}.data(),
+            data: crate::instruction::InitializeVault { vault_index: 0 }.data(),
         };
 
         let transaction = Transaction::new_signed_with_payer(
@@ -1179,7 +1624,9 @@ mod tests {
             address: payer.pubkey(),
             mint: mint.pubkey(),
             whitelist_PDA: whitelist_acc,
-            system_program : SYSTEM_PROGRAM_ID
+            system_program : SYSTEM_PROGRAM_ID,
+            event_authority: hook_event_authority(),
+            program: transfer_hook::ID,
         };
 
         let ix = Instruction {
@@ -1230,6 +1677,12 @@ mod tests {
 
         let (registryPDA, r_bump) = Pubkey::find_program_address(seeds, &crate::ID);
 
+        let depositor_share_account = associated_token::get_associated_token_address_with_program_id(
+            &payer_pubkey,
+            &share_mint,
+            &TOKEN_PROGRAM_ID,
+        );
+
         initialize_extra_account_metas(
             &mut program,
             &payer,
@@ -1243,12 +1696,17 @@ mod tests {
             mint: mint.pubkey(),
             depositor_token_account: depositor_ata,
             vault_token_reserve: reserve_ata,
-            transfer_hook_program: transfer_hook_program_id,
-            extra_account_meta_list,
-            depositor_whitelist_PDA:depositor_whitelist,
+            share_mint,
+            depositor_share_account,
+            transfer_hook_program: Some(transfer_hook_program_id),
+            extra_account_meta_list: Some(extra_account_meta_list),
+            depositor_whitelist_PDA:Some(depositor_whitelist),
             associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
             token_program: TOKEN_PROGRAM_ID,
+            share_token_program: TOKEN_PROGRAM_ID,
             system_program: SYSTEM_PROGRAM_ID,
+            event_authority: event_authority(),
+            program: PROGRAM_ID,
         };
 
         msg!("depositor: {}", payer_pubkey);
@@ -1267,6 +1725,8 @@ mod tests {
             accounts: accounts.to_account_metas(None),
             data: crate::instruction::Deposit {
                 amount: deposit_amount,
+                lock_duration: 0,
+                max_fee: 0,
             }
             .data(),
         };
@@ -1301,6 +1761,20 @@ mod tests {
         assert_eq!(vault.token_reserve_amount, deposit_amount);
         assert_eq!(vault.num_depositors, 1);
 
+        let registry_account = program.get_account(&registryPDA).expect("Registry entry should exist");
+        let mut registry_data: &[u8] = &registry_account.data;
+        let registry: crate::state::VaultRegistryEntry =
+            anchor_lang::AccountDeserialize::try_deserialize(&mut registry_data)
+                .expect("Failed to deserialize registry");
+
+        // First deposit into an empty vault is priced 1:1
+        assert_eq!(registry.shares, deposit_amount);
+
+        let share_account = program.get_account(&depositor_share_account).expect("Depositor share account should exist");
+        let share_state = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&share_account.data)
+            .expect("Failed to unpack share account");
+        assert_eq!(share_state.base.amount, deposit_amount, "Depositor should hold the minted shares");
+
         // Verify depositor balance decreased
         let mut depositor_ata_account = program.get_account(&depositor_ata).expect("Depositor ATA should exist");
         // assert!(depositor_ata_account.is_initialized());
@@ -1355,6 +1829,8 @@ mod tests {
             hook_program_id: transfer_hook::ID,
             system_program: SYSTEM_PROGRAM_ID,
             token_program: TOKEN_PROGRAM_ID,
+            event_authority: event_authority(),
+            program: PROGRAM_ID,
         };
 
         let init_mint_ix = Instruction {
@@ -1374,7 +1850,13 @@ mod tests {
 
         // Initialize vault
         let (vault_pda, _) =
-            Pubkey::find_program_address(&[b"vault", payer_pubkey.as_ref()], &PROGRAM_ID);
+            Pubkey::find_program_address(
+                &[b"vault", payer_pubkey.as_ref(), mint.pubkey().as_ref(), &0u64.to_le_bytes()],
+                &PROGRAM_ID,
+            );
+
+        let (share_mint, _) =
+            Pubkey::find_program_address(&[b"share_mint", vault_pda.as_ref()], &PROGRAM_ID);
 
         let reserve_ata = associated_token::get_associated_token_address_with_program_id(
             &vault_pda,
@@ -1389,18 +1871,21 @@ mod tests {
         let accounts = crate::accounts::InitializeVault {
             vault_authority: payer_pubkey,
             mint: mint.pubkey(),
-            hook_program_id: transfer_hook_program_id,
             vault: vault_pda,
+            share_mint,
             token_reserve: reserve_ata,
             associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
             token_program: TOKEN_PROGRAM_ID,
+            share_token_program: TOKEN_PROGRAM_ID,
             system_program: SYSTEM_PROGRAM_ID,
+            event_authority: event_authority(),
+            program: PROGRAM_ID,
         };
 
         let init_vault_ix = Instruction {
             program_id: PROGRAM_ID,
             accounts: accounts.to_account_metas(None),
-            data: crate::instruction::InitializeVault {}.data(),
+            data: crate::instruction::InitializeVault { vault_index: 0 }.data(),
         };
 
         let transaction = Transaction::new_signed_with_payer(
@@ -1425,7 +1910,9 @@ mod tests {
             address: payer.pubkey(),
             mint: mint.pubkey(),
             whitelist_PDA: whitelist_acc,
-            system_program : SYSTEM_PROGRAM_ID
+            system_program : SYSTEM_PROGRAM_ID,
+            event_authority: hook_event_authority(),
+            program: transfer_hook::ID,
         };
 
         let ix = Instruction {
@@ -1475,6 +1962,12 @@ mod tests {
 
         let (registryPDA, r_bump) = Pubkey::find_program_address(seeds, &crate::ID);
 
+        let depositor_share_account = associated_token::get_associated_token_address_with_program_id(
+            &payer_pubkey,
+            &share_mint,
+            &TOKEN_PROGRAM_ID,
+        );
+
         initialize_extra_account_metas(
             &mut program,
             &payer,
@@ -1488,12 +1981,17 @@ mod tests {
             mint: mint.pubkey(),
             depositor_token_account: depositor_ata,
             vault_token_reserve: reserve_ata,
-            transfer_hook_program: transfer_hook_program_id,
-            extra_account_meta_list,
-            depositor_whitelist_PDA:depositor_whitelist,
+            share_mint,
+            depositor_share_account,
+            transfer_hook_program: Some(transfer_hook_program_id),
+            extra_account_meta_list: Some(extra_account_meta_list),
+            depositor_whitelist_PDA:Some(depositor_whitelist),
             associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
             token_program: TOKEN_PROGRAM_ID,
+            share_token_program: TOKEN_PROGRAM_ID,
             system_program: SYSTEM_PROGRAM_ID,
+            event_authority: event_authority(),
+            program: PROGRAM_ID,
         };
 
         msg!("depositor: {}", payer_pubkey);
@@ -1512,6 +2010,8 @@ mod tests {
             accounts: accounts.to_account_metas(None),
             data: crate::instruction::Deposit {
                 amount: deposit_amount,
+                lock_duration: 0,
+                max_fee: 0,
             }
                 .data(),
         };
@@ -1537,6 +2037,9 @@ mod tests {
         assert!(tx_result.is_ok(), "Deposit should succeed");
         
         // Now test withdraw
+        // The vault owns the reserve, so the transfer hook checks the vault's whitelist entry
+        let vault_whitelist = add_to_whitelist(&mut program, &payer, &mint.pubkey(), &vault_pda);
+
         let withdraw_amount = 300u64;
         let withdraw_ix = Instruction {
             program_id: PROGRAM_ID,
@@ -1545,18 +2048,29 @@ mod tests {
                 vault: vault_pda,
                 vault_registry_entry: registryPDA,
                 mint: mint.pubkey(),
-                withdrawer_token_account: depositor_ata,
+                recipient_token_account: depositor_ata,
+                recipient_whitelist: Some(Pubkey::find_program_address(
+                    &[b"whitelist", mint.pubkey().as_ref(), payer_pubkey.as_ref()],
+                    &transfer_hook_program_id,
+                )
+                .0),
                 vault_token_reserve: reserve_ata,
-                extra_account_meta_list,
-                transfer_hook_program: transfer_hook_program_id,
-                withdrawer_whitelist_PDA: depositor_whitelist,
+                share_mint,
+                withdrawer_share_account: depositor_share_account,
+                extra_account_meta_list: Some(extra_account_meta_list),
+                transfer_hook_program: Some(transfer_hook_program_id),
+                vault_whitelist: Some(vault_whitelist),
                 associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
                 token_program: TOKEN_PROGRAM_ID,
+                share_token_program: TOKEN_PROGRAM_ID,
                 system_program: SYSTEM_PROGRAM_ID,
+                event_authority: event_authority(),
+                program: PROGRAM_ID,
             }
             .to_account_metas(None),
             data: crate::instruction::Withdraw {
                 amount: withdraw_amount,
+                max_fee: 0,
             }
             .data(),
         };
@@ -1597,6 +2111,7 @@ mod tests {
                 .expect("Failed to deserialize registry");
 
         assert_eq!(registry.token_balance, deposit_amount - withdraw_amount);
+        assert_eq!(registry.shares, deposit_amount - withdraw_amount);
     
         println!("✅ Withdraw test passed");
         println!("   Withdrew: {} tokens", withdraw_amount);
@@ -1752,4 +2267,2708 @@ mod tests {
     //
     //     println!("✅ Over-withdraw correctly failed");
     // }
+
+    #[test]
+    fn test_redeem_by_depositor() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+
+        let depositor = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let depositor_ata = fund_user(&mut program, &payer, &fixture, &depositor.pubkey(), 1_000);
+
+        let res = send(&mut program, &[deposit_ix(&fixture, &depositor.pubkey(), 600)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+
+        let redeem_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: withdraw_accounts(&fixture, &depositor.pubkey()),
+            data: crate::instruction::Redeem { shares: 600, max_fee: 0 }.data(),
+        };
+        let res = send(&mut program, &[redeem_ix], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Depositor should be able to redeem their own shares: {:?}", res.err());
+
+        let registry = get_registry(&program, &registry_address(&fixture, &depositor.pubkey()));
+        assert_eq!(registry.shares, 0);
+        assert_eq!(registry.token_balance, 0);
+        assert_eq!(token_balance(&program, &depositor_ata), 1_000);
+        assert_eq!(get_vault(&program, &fixture.vault).token_reserve_amount, 0);
+    }
+
+    #[test]
+    fn test_withdraw_from_other_position_fails() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+
+        let depositor = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        fund_user(&mut program, &payer, &fixture, &depositor.pubkey(), 1_000);
+
+        let res = send(&mut program, &[deposit_ix(&fixture, &depositor.pubkey(), 600)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+
+        // The vault authority has no position of its own and cannot touch the depositor's
+        let mut accounts = withdraw_accounts(&fixture, &payer.pubkey());
+        accounts[2].pubkey = registry_address(&fixture, &depositor.pubkey());
+        let withdraw_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts,
+            data: crate::instruction::Withdraw { amount: 100, max_fee: 0 }.data(),
+        };
+        let res = send(&mut program, &[withdraw_ix], &payer, &[&payer]);
+        assert!(res.is_err(), "Withdrawing from someone else's position should fail");
+
+        // Shares cannot be moved to another wallet either
+        let share_mint_account = program.get_account(&fixture.share_mint).unwrap();
+        let share_mint_state = StateWithExtensions::<Token2022Mint>::unpack(&share_mint_account.data).unwrap();
+        assert!(share_mint_state.get_extension_types().unwrap().contains(&ExtensionType::NonTransferable));
+        let payer_share_account = create_ata(&mut program, &payer, &payer.pubkey(), &fixture.share_mint);
+        let transfer_shares_ix = spl_token_2022::instruction::transfer_checked(
+            &TOKEN_PROGRAM_ID,
+            &share_account_address(&fixture, &depositor.pubkey()),
+            &fixture.share_mint,
+            &payer_share_account,
+            &depositor.pubkey(),
+            &[],
+            100,
+            share_mint_state.base.decimals,
+        )
+        .unwrap();
+        let res = send(&mut program, &[transfer_shares_ix], &depositor, &[&depositor]);
+        assert!(res.is_err(), "Shares should not be transferable");
+
+        assert_eq!(get_registry(&program, &registry_address(&fixture, &depositor.pubkey())).shares, 600);
+        assert_eq!(token_balance(&program, &share_account_address(&fixture, &depositor.pubkey())), 600);
+    }
+
+    #[test]
+    fn test_admin_withdraw() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+
+        let depositor = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        fund_user(&mut program, &payer, &fixture, &depositor.pubkey(), 1_000);
+        let res = send(&mut program, &[deposit_ix(&fixture, &depositor.pubkey(), 600)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+
+        let authority_ata = create_ata(&mut program, &payer, &payer.pubkey(), &fixture.mint.pubkey());
+
+        let admin_withdraw_ix = |authority: &Pubkey, authority_token_account: Pubkey| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::AdminWithdraw {
+                vault_authority: *authority,
+                vault: fixture.vault,
+                mint: fixture.mint.pubkey(),
+                authority_token_account,
+                vault_token_reserve: fixture.reserve,
+                extra_account_meta_list: Some(fixture.extra_account_meta_list),
+                transfer_hook_program: Some(transfer_hook::ID),
+                vault_whitelist: Some(fixture.vault_whitelist),
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::AdminWithdraw { amount: 200 }.data(),
+        };
+
+        let depositor_ata = associated_token::get_associated_token_address_with_program_id(
+            &depositor.pubkey(),
+            &fixture.mint.pubkey(),
+            &TOKEN_PROGRAM_ID,
+        );
+        let res = send(&mut program, &[admin_withdraw_ix(&depositor.pubkey(), depositor_ata)], &depositor, &[&depositor]);
+        assert!(res.is_err(), "Only the vault authority can use the admin withdraw");
+
+        let res = send(&mut program, &[admin_withdraw_ix(&payer.pubkey(), authority_ata)], &payer, &[&payer]);
+        assert!(res.is_ok(), "Admin withdraw should succeed: {:?}", res.err());
+
+        assert_eq!(token_balance(&program, &authority_ata), 200);
+        assert_eq!(get_vault(&program, &fixture.vault).token_reserve_amount, 400);
+    }
+
+    #[test]
+    fn test_multiple_vaults_per_authority_and_mint() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+        let mint = fixture.mint.pubkey();
+
+        // Same authority and mint, different index
+        let (init_vault_ix, second_vault, _, second_reserve) =
+            initialize_vault_ix(&payer.pubkey(), &mint, 1);
+        let res = send(&mut program, &[init_vault_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Second vault for the same mint should be created: {:?}", res.err());
+
+        assert_ne!(second_vault, fixture.vault);
+        let vault = get_vault(&program, &second_vault);
+        assert_eq!(vault.vault_index, 1);
+        assert_eq!(vault.mint, mint);
+        assert_eq!(vault.token_reserve, second_reserve);
+
+        // Re-using an index is rejected
+        let (init_vault_ix, _, _, _) = initialize_vault_ix(&payer.pubkey(), &mint, 1);
+        program.expire_blockhash();
+        let res = send(&mut program, &[init_vault_ix], &payer, &[&payer]);
+        assert!(res.is_err(), "A vault index can only be used once per authority and mint");
+    }
+
+    #[test]
+    fn test_interest_accrual_on_registry() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+
+        let depositor = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        fund_user(&mut program, &payer, &fixture, &depositor.pubkey(), 2_000_000_000);
+
+        let res = send(&mut program, &[deposit_ix(&fixture, &depositor.pubkey(), 1_000_000_000)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+
+        let registry_pda = registry_address(&fixture, &depositor.pubkey());
+        let registry = get_registry(&program, &registry_pda);
+        assert_eq!(registry.accrued_interest, 0);
+
+        // One year at 5%
+        let mut clock = program.get_sysvar::<Clock>();
+        clock.unix_timestamp += 31_556_736;
+        program.set_sysvar::<Clock>(&clock);
+
+        let res = send(&mut program, &[deposit_ix(&fixture, &depositor.pubkey(), 1_000)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Second deposit should succeed: {:?}", res.err());
+
+        // 1_000_000_000 * (e^0.05 - 1)
+        let registry = get_registry(&program, &registry_pda);
+        assert!((registry.accrued_interest - 51_271_096).abs() <= 1, "Unexpected interest: {}", registry.accrued_interest);
+        assert_eq!(registry.last_accrual_timestamp, clock.unix_timestamp);
+        assert!(registry.interest_inclusive_balance > registry.token_balance);
+    }
+
+    #[test]
+    fn test_apply_interest_matches_token_2022() {
+        use crate::utils::apply_interest;
+
+        let seconds_per_year: i64 = 31_556_736;
+        let config = InterestBearingConfig {
+            rate_authority: Default::default(),
+            initialization_timestamp: 0.into(),
+            pre_update_average_rate: 500.into(),
+            last_update_timestamp: seconds_per_year.into(),
+            current_rate: 500.into(),
+        };
+
+        // Token-2022 displays 1.0512710963760241 for 10_000_000_000 raw units at 10 decimals
+        assert_eq!(apply_interest(&config, 10_000_000_000, seconds_per_year).unwrap(), 10_512_710_963);
+        assert_eq!(apply_interest(&config, 0, seconds_per_year).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_realize_interest() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+        let mint = fixture.mint.pubkey();
+
+        let depositor = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        fund_user(&mut program, &payer, &fixture, &depositor.pubkey(), 1_000_000_000);
+        let res = send(&mut program, &[deposit_ix(&fixture, &depositor.pubkey(), 1_000_000_000)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+
+        let (mint_authority, _) =
+            Pubkey::find_program_address(&[b"mint_authority", fixture.vault.as_ref()], &PROGRAM_ID);
+        let realize_interest_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::RealizeInterest {
+                vault: fixture.vault,
+                mint,
+                mint_authority,
+                vault_token_reserve: fixture.reserve,
+                token_program: TOKEN_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::RealizeInterest {}.data(),
+        };
+
+        // One year at 5%
+        let mut clock = program.get_sysvar::<Clock>();
+        clock.unix_timestamp += 31_556_736;
+        program.set_sysvar::<Clock>(&clock);
+
+        let res = send(&mut program, std::slice::from_ref(&realize_interest_ix), &payer, &[&payer]);
+        assert!(res.is_err(), "Realizing interest requires the mint authority to be handed over");
+
+        let set_authority_ix = spl_token_2022::instruction::set_authority(
+            &TOKEN_PROGRAM_ID,
+            &mint,
+            Some(&mint_authority),
+            spl_token_2022::instruction::AuthorityType::MintTokens,
+            &payer.pubkey(),
+            &[],
+        )
+        .unwrap();
+        let res = send(&mut program, &[set_authority_ix, realize_interest_ix.clone()], &payer, &[&payer]);
+        assert!(res.is_ok(), "Realizing interest should succeed: {:?}", res.err());
+
+        // 1_000_000_000 * (e^0.05 - 1)
+        let reserve = token_balance(&program, &fixture.reserve);
+        assert!((reserve as i64 - 1_051_271_096).abs() <= 1, "Unexpected reserve: {}", reserve);
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.token_reserve_amount, reserve);
+        assert_eq!(vault.last_interest_realization, clock.unix_timestamp);
+
+        // Nothing more to mint until time passes again
+        program.expire_blockhash();
+        let res = send(&mut program, &[realize_interest_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Realizing interest twice should succeed: {:?}", res.err());
+        assert_eq!(token_balance(&program, &fixture.reserve), reserve);
+    }
+
+    #[test]
+    fn test_realize_interest_only_for_time_in_reserve() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+        let mint = fixture.mint.pubkey();
+
+        let early = Keypair::new();
+        let late = Keypair::new();
+        for user in [&early, &late] {
+            program.airdrop(&user.pubkey(), LAMPORTS_PER_SOL).unwrap();
+            fund_user(&mut program, &payer, &fixture, &user.pubkey(), 1_000_000_000);
+        }
+        let res = send(&mut program, &[deposit_ix(&fixture, &early.pubkey(), 1_000_000_000)], &early, &[&early]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+
+        let realize_interest_ix = |vault: Pubkey, reserve: Pubkey| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::RealizeInterest {
+                vault,
+                mint,
+                mint_authority: Pubkey::find_program_address(&[b"mint_authority", vault.as_ref()], &PROGRAM_ID).0,
+                vault_token_reserve: reserve,
+                token_program: TOKEN_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::RealizeInterest {}.data(),
+        };
+
+        let (mint_authority, _) =
+            Pubkey::find_program_address(&[b"mint_authority", fixture.vault.as_ref()], &PROGRAM_ID);
+        let set_authority_ix = spl_token_2022::instruction::set_authority(
+            &TOKEN_PROGRAM_ID,
+            &mint,
+            Some(&mint_authority),
+            spl_token_2022::instruction::AuthorityType::MintTokens,
+            &payer.pubkey(),
+            &[],
+        )
+        .unwrap();
+        let res = send(&mut program, &[set_authority_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Handing over the mint authority should succeed: {:?}", res.err());
+
+        // Another vault of the same mint cannot use the delegated mint authority
+        let attacker = Keypair::new();
+        program.airdrop(&attacker.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let (init_vault_ix, attacker_vault, _, attacker_reserve) =
+            initialize_vault_ix(&attacker.pubkey(), &mint, 0);
+        let res = send(&mut program, &[init_vault_ix], &attacker, &[&attacker]);
+        assert!(res.is_ok(), "Initializing a second vault should succeed: {:?}", res.err());
+        let res = send(&mut program, &[realize_interest_ix(attacker_vault, attacker_reserve)], &attacker, &[&attacker]);
+        assert!(res.unwrap_err().contains("MintAuthorityNotDelegated"));
+
+        // One year at 5%, then a second deposit right before realizing
+        let mut clock = program.get_sysvar::<Clock>();
+        clock.unix_timestamp += 31_556_736;
+        program.set_sysvar::<Clock>(&clock);
+
+        let res = send(&mut program, &[deposit_ix(&fixture, &late.pubkey(), 1_000_000_000)], &late, &[&late]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+        let res = send(&mut program, &[realize_interest_ix(fixture.vault, fixture.reserve)], &payer, &[&payer]);
+        assert!(res.is_ok(), "Realizing interest should succeed: {:?}", res.err());
+
+        // Only the first deposit earned interest, 1_000_000_000 * (e^0.05 - 1)
+        let reserve = token_balance(&program, &fixture.reserve);
+        assert!((reserve as i64 - 2_051_271_096).abs() <= 1, "Unexpected reserve: {}", reserve);
+
+        // The late depositor's shares were priced with the pending interest included,
+        // so the interest stays with the early depositor
+        let total_assets = get_vault(&program, &fixture.vault).token_reserve_amount as u128;
+        let early_shares = get_registry(&program, &registry_address(&fixture, &early.pubkey())).shares as u128;
+        let late_shares = get_registry(&program, &registry_address(&fixture, &late.pubkey())).shares as u128;
+        let early_value = early_shares * total_assets / (early_shares + late_shares);
+        let late_value = late_shares * total_assets / (early_shares + late_shares);
+        assert!((early_value as i64 - 1_051_271_096).abs() <= 2, "Unexpected early value: {}", early_value);
+        assert!((late_value as i64 - 1_000_000_000).abs() <= 2, "Unexpected late value: {}", late_value);
+    }
+
+    #[test]
+    fn test_rate_change_checkpoints_interest() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+        let mint = fixture.mint.pubkey();
+
+        let depositor = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        fund_user(&mut program, &payer, &fixture, &depositor.pubkey(), 1_000_000_000);
+        let res = send(&mut program, &[deposit_ix(&fixture, &depositor.pubkey(), 1_000_000_000)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+
+        let (pending_rate_change, _) =
+            Pubkey::find_program_address(&[b"pending_rate", fixture.vault.as_ref()], &PROGRAM_ID);
+        let (rate_history, _) =
+            Pubkey::find_program_address(&[b"rate_history", fixture.vault.as_ref()], &PROGRAM_ID);
+        let (mint_authority, _) =
+            Pubkey::find_program_address(&[b"mint_authority", fixture.vault.as_ref()], &PROGRAM_ID);
+
+        let set_rate_authority_ix = spl_token_2022::instruction::set_authority(
+            &TOKEN_PROGRAM_ID,
+            &mint,
+            Some(&fixture.vault),
+            spl_token_2022::instruction::AuthorityType::InterestRate,
+            &payer.pubkey(),
+            &[],
+        )
+        .unwrap();
+        let set_mint_authority_ix = spl_token_2022::instruction::set_authority(
+            &TOKEN_PROGRAM_ID,
+            &mint,
+            Some(&mint_authority),
+            spl_token_2022::instruction::AuthorityType::MintTokens,
+            &payer.pubkey(),
+            &[],
+        )
+        .unwrap();
+        let propose_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::ProposeRateChange {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+                pending_rate_change,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::ProposeRateChange { new_rate: 1_000 }.data(),
+        };
+        let res = send(
+            &mut program,
+            &[set_rate_authority_ix, set_mint_authority_ix, propose_ix],
+            &payer,
+            &[&payer],
+        );
+        assert!(res.is_ok(), "Proposing the rate change should succeed: {:?}", res.err());
+
+        // One year at 5%, then the rate doubles for a second year
+        let mut clock = program.get_sysvar::<Clock>();
+        clock.unix_timestamp += 31_556_736;
+        program.set_sysvar::<Clock>(&clock);
+
+        let execute_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::ExecuteRateChange {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+                mint,
+                pending_rate_change,
+                rate_history,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::ExecuteRateChange {}.data(),
+        };
+        let res = send(&mut program, &[execute_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Rate change should execute: {:?}", res.err());
+
+        // The first year is booked at the old rate, 1_000_000_000 * (e^0.05 - 1)
+        let unrealized = get_vault(&program, &fixture.vault).unrealized_interest;
+        assert!((unrealized as i64 - 51_271_096).abs() <= 1, "Unexpected interest: {}", unrealized);
+
+        let mut clock = program.get_sysvar::<Clock>();
+        clock.unix_timestamp += 31_556_736;
+        program.set_sysvar::<Clock>(&clock);
+
+        let realize_interest_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::RealizeInterest {
+                vault: fixture.vault,
+                mint,
+                mint_authority,
+                vault_token_reserve: fixture.reserve,
+                token_program: TOKEN_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::RealizeInterest {}.data(),
+        };
+        let res = send(&mut program, &[realize_interest_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Realizing interest should succeed: {:?}", res.err());
+
+        // Plus the second year at the new rate, 1_000_000_000 * (e^0.1 - 1)
+        let reserve = token_balance(&program, &fixture.reserve);
+        assert!((reserve as i64 - 1_156_442_014).abs() <= 2, "Unexpected reserve: {}", reserve);
+    }
+
+    #[test]
+    fn test_timelocked_rate_change() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+        let mint = fixture.mint.pubkey();
+
+        let (pending_rate_change, _) =
+            Pubkey::find_program_address(&[b"pending_rate", fixture.vault.as_ref()], &PROGRAM_ID);
+        let (rate_history, _) =
+            Pubkey::find_program_address(&[b"rate_history", fixture.vault.as_ref()], &PROGRAM_ID);
+
+        let propose_ix = |new_rate: i16| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::ProposeRateChange {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+                pending_rate_change,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::ProposeRateChange { new_rate }.data(),
+        };
+        let execute_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::ExecuteRateChange {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+                mint,
+                pending_rate_change,
+                rate_history,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::ExecuteRateChange {}.data(),
+        };
+        let cancel_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::CancelRateChange {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+                pending_rate_change,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::CancelRateChange {}.data(),
+        };
+
+        let set_authority_ix = spl_token_2022::instruction::set_authority(
+            &TOKEN_PROGRAM_ID,
+            &mint,
+            Some(&fixture.vault),
+            spl_token_2022::instruction::AuthorityType::InterestRate,
+            &payer.pubkey(),
+            &[],
+        )
+        .unwrap();
+        let set_bounds_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::VaultAdmin {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::SetRateBounds { min_rate: 0, max_rate: 1_000 }.data(),
+        };
+        let res = send(&mut program, &[set_authority_ix, set_bounds_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Handing over the rate authority should succeed: {:?}", res.err());
+
+        let res = send(&mut program, &[propose_ix(1_500)], &payer, &[&payer]);
+        assert!(res.is_err(), "Rates above the maximum should be rejected");
+
+        // A cancelled proposal frees the slot for a new one
+        let res = send(&mut program, &[propose_ix(100)], &payer, &[&payer]);
+        assert!(res.is_ok(), "Proposal should succeed: {:?}", res.err());
+        let res = send(&mut program, &[cancel_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Cancel should succeed: {:?}", res.err());
+        assert!(program.get_account(&pending_rate_change).is_none_or(|a| a.data.is_empty()));
+
+        let res = send(&mut program, &[propose_ix(750)], &payer, &[&payer]);
+        assert!(res.is_ok(), "Proposal should succeed: {:?}", res.err());
+
+        // The pending rate is visible before it takes effect
+        let pending_account = program.get_account(&pending_rate_change).unwrap();
+        let pending: crate::state::PendingRateChange =
+            anchor_lang::AccountDeserialize::try_deserialize(&mut pending_account.data.as_slice()).unwrap();
+        assert_eq!(pending.new_rate, 750);
+        assert_eq!(pending.executable_at, pending.proposed_at + crate::state::DEFAULT_RATE_CHANGE_DELAY);
+
+        let res = send(&mut program, std::slice::from_ref(&execute_ix), &payer, &[&payer]);
+        assert!(res.is_err(), "Executing before the delay has elapsed should fail");
+
+        let mut clock = program.get_sysvar::<Clock>();
+        clock.unix_timestamp = pending.executable_at;
+        program.set_sysvar::<Clock>(&clock);
+
+        program.expire_blockhash();
+        let res = send(&mut program, &[execute_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Rate change should execute after the delay: {:?}", res.err());
+
+        let mint_account = program.get_account(&mint).unwrap();
+        let mint_state = StateWithExtensions::<Token2022Mint>::unpack(&mint_account.data).unwrap();
+        let config = mint_state.get_extension::<InterestBearingConfig>().unwrap();
+        assert_eq!(i16::from(config.current_rate), 750);
+
+        let history_account = program.get_account(&rate_history).unwrap();
+        let history: crate::state::RateHistory =
+            anchor_lang::AccountDeserialize::try_deserialize(&mut history_account.data.as_slice()).unwrap();
+        assert_eq!(history.total_changes, 1);
+        assert_eq!(history.changes[0].old_rate, 500);
+        assert_eq!(history.changes[0].new_rate, 750);
+        assert_eq!(history.changes[0].signer, payer.pubkey());
+    }
+
+    #[test]
+    fn test_rate_history_records_changes() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+        let mint = fixture.mint.pubkey();
+
+        let (pending_rate_change, _) =
+            Pubkey::find_program_address(&[b"pending_rate", fixture.vault.as_ref()], &PROGRAM_ID);
+        let (rate_history, _) =
+            Pubkey::find_program_address(&[b"rate_history", fixture.vault.as_ref()], &PROGRAM_ID);
+
+        let propose_ix = |new_rate: i16| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::ProposeRateChange {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+                pending_rate_change,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::ProposeRateChange { new_rate }.data(),
+        };
+        let execute_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::ExecuteRateChange {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+                mint,
+                pending_rate_change,
+                rate_history,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::ExecuteRateChange {}.data(),
+        };
+
+        let set_authority_ix = spl_token_2022::instruction::set_authority(
+            &TOKEN_PROGRAM_ID,
+            &mint,
+            Some(&fixture.vault),
+            spl_token_2022::instruction::AuthorityType::InterestRate,
+            &payer.pubkey(),
+            &[],
+        )
+        .unwrap();
+        let res = send(&mut program, &[set_authority_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Handing over the rate authority should succeed: {:?}", res.err());
+
+        let mut executed_at = Vec::new();
+        for new_rate in [750, 300] {
+            program.expire_blockhash();
+            let res = send(&mut program, &[propose_ix(new_rate)], &payer, &[&payer]);
+            assert!(res.is_ok(), "Proposal should succeed: {:?}", res.err());
+
+            let pending_account = program.get_account(&pending_rate_change).unwrap();
+            let pending: crate::state::PendingRateChange =
+                anchor_lang::AccountDeserialize::try_deserialize(&mut pending_account.data.as_slice()).unwrap();
+            let mut clock = program.get_sysvar::<Clock>();
+            clock.unix_timestamp = pending.executable_at;
+            program.set_sysvar::<Clock>(&clock);
+
+            program.expire_blockhash();
+            let res = send(&mut program, std::slice::from_ref(&execute_ix), &payer, &[&payer]);
+            assert!(res.is_ok(), "Rate change should execute after the delay: {:?}", res.err());
+            executed_at.push(pending.executable_at);
+        }
+
+        // Entries are appended oldest first, each chaining from the previous rate
+        let history_account = program.get_account(&rate_history).unwrap();
+        let history: crate::state::RateHistory =
+            anchor_lang::AccountDeserialize::try_deserialize(&mut history_account.data.as_slice()).unwrap();
+        assert_eq!(history.vault, fixture.vault);
+        assert_eq!(history.total_changes, 2);
+        assert_eq!(history.changes.len(), 2);
+        assert_eq!((history.changes[0].old_rate, history.changes[0].new_rate), (500, 750));
+        assert_eq!((history.changes[1].old_rate, history.changes[1].new_rate), (750, 300));
+        assert_eq!(history.changes[0].timestamp, executed_at[0]);
+        assert_eq!(history.changes[1].timestamp, executed_at[1]);
+        assert!(history.changes[0].timestamp < history.changes[1].timestamp);
+        assert!(history.changes.iter().all(|change| change.signer == payer.pubkey()));
+    }
+
+    #[test]
+    fn test_rate_change_delay_decrease_waits() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+
+        let set_delay_ix = |delay: i64| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::VaultAdmin {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::SetRateChangeDelay { delay }.data(),
+        };
+
+        // Raising the delay applies at once
+        let delay = crate::state::DEFAULT_RATE_CHANGE_DELAY * 2;
+        let res = send(&mut program, &[set_delay_ix(delay)], &payer, &[&payer]);
+        assert!(res.is_ok(), "Raising the delay should succeed: {:?}", res.err());
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.rate_change_delay, delay);
+        assert_eq!(vault.pending_rate_change_delay_at, 0);
+
+        // Lowering it waits out the current delay
+        let res = send(&mut program, &[set_delay_ix(0)], &payer, &[&payer]);
+        assert!(res.is_ok(), "Lowering the delay should succeed: {:?}", res.err());
+        let now = program.get_sysvar::<Clock>().unix_timestamp;
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.rate_change_delay, delay);
+        assert_eq!(vault.pending_rate_change_delay, 0);
+        assert_eq!(vault.pending_rate_change_delay_at, now + delay);
+        assert_eq!(vault.effective_rate_change_delay(now), delay);
+        assert_eq!(vault.effective_rate_change_delay(now + delay), 0);
+
+        let mut clock = program.get_sysvar::<Clock>();
+        clock.unix_timestamp = now + delay;
+        program.set_sysvar::<Clock>(&clock);
+
+        // The next change settles the lowered delay first
+        program.expire_blockhash();
+        let res = send(&mut program, &[set_delay_ix(60)], &payer, &[&payer]);
+        assert!(res.is_ok(), "Setting the delay should succeed: {:?}", res.err());
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.rate_change_delay, 60);
+        assert_eq!(vault.pending_rate_change_delay_at, 0);
+    }
+
+    #[test]
+    fn test_pause_vault() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+
+        let depositor = Keypair::new();
+        let pauser = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        program.airdrop(&pauser.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        fund_user(&mut program, &payer, &fixture, &depositor.pubkey(), 1_000);
+
+        let roles = Pubkey::find_program_address(&[b"vault_roles", fixture.vault.as_ref()], &PROGRAM_ID).0;
+        let pause_ix = |signer: &Pubkey, pause: bool, deposits: bool, withdrawals: bool| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::PauseVault {
+                pauser: *signer,
+                vault: fixture.vault,
+                vault_roles: Some(roles),
+            }
+            .to_account_metas(None),
+            data: if pause {
+                crate::instruction::PauseVault { deposits, withdrawals }.data()
+            } else {
+                crate::instruction::UnpauseVault { deposits, withdrawals }.data()
+            },
+        };
+
+        let grant_pauser_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::ManageRoles {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: roles,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::GrantRole { role: crate::state::Role::Pauser, member: pauser.pubkey() }.data(),
+        };
+        let res = send(&mut program, &[grant_pauser_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Granting the pauser role should succeed: {:?}", res.err());
+
+        let res = send(&mut program, &[deposit_ix(&fixture, &depositor.pubkey(), 600)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+
+        program.expire_blockhash();
+        let res = send(&mut program, &[pause_ix(&pauser.pubkey(), true, true, false)], &pauser, &[&pauser]);
+        assert!(res.is_ok(), "Pauser should be able to pause deposits: {:?}", res.err());
+        let vault = get_vault(&program, &fixture.vault);
+        assert!(vault.deposits_paused);
+        assert!(!vault.withdrawals_paused);
+
+        let res = send(&mut program, &[deposit_ix(&fixture, &depositor.pubkey(), 100)], &depositor, &[&depositor]);
+        assert!(res.is_err(), "Deposits should be rejected while paused");
+        assert!(res.unwrap_err().contains("VaultPaused"));
+
+        // Withdrawals are paused separately
+        let withdraw_ix = |amount: u64| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: withdraw_accounts(&fixture, &depositor.pubkey()),
+            data: crate::instruction::Withdraw { amount, max_fee: 0 }.data(),
+        };
+        let res = send(&mut program, &[withdraw_ix(100)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Withdrawals should still work: {:?}", res.err());
+
+        let res = send(&mut program, &[pause_ix(&payer.pubkey(), true, false, true)], &payer, &[&payer]);
+        assert!(res.is_ok(), "Authority should be able to pause withdrawals: {:?}", res.err());
+        let res = send(&mut program, &[withdraw_ix(200)], &depositor, &[&depositor]);
+        assert!(res.is_err(), "Withdrawals should be rejected while paused");
+
+        let res = send(&mut program, &[pause_ix(&pauser.pubkey(), false, true, true)], &pauser, &[&pauser]);
+        assert!(res.is_ok(), "Pauser should be able to unpause: {:?}", res.err());
+        program.expire_blockhash();
+        let res = send(&mut program, &[deposit_ix(&fixture, &depositor.pubkey(), 100)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit should succeed after unpausing: {:?}", res.err());
+        let res = send(&mut program, &[withdraw_ix(200)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Withdraw should succeed after unpausing: {:?}", res.err());
+    }
+
+    #[test]
+    fn test_deposit_caps() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+
+        let alice = Keypair::new();
+        let bob = Keypair::new();
+        for user in [&alice, &bob] {
+            program.airdrop(&user.pubkey(), LAMPORTS_PER_SOL).unwrap();
+            fund_user(&mut program, &payer, &fixture, &user.pubkey(), 1_000);
+        }
+
+        let set_caps_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::VaultAdmin {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::SetDepositCaps {
+                max_total_deposits: 1_000,
+                max_deposit_per_user: 600,
+            }
+            .data(),
+        };
+        let res = send(&mut program, &[set_caps_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Setting deposit caps should succeed: {:?}", res.err());
+
+        let res = send(&mut program, &[deposit_ix(&fixture, &alice.pubkey(), 500)], &alice, &[&alice]);
+        assert!(res.is_ok(), "Deposit within the caps should succeed: {:?}", res.err());
+
+        let res = send(&mut program, &[deposit_ix(&fixture, &alice.pubkey(), 200)], &alice, &[&alice]);
+        assert!(res.unwrap_err().contains("UserDepositCapExceeded"));
+
+        let res = send(&mut program, &[deposit_ix(&fixture, &bob.pubkey(), 600)], &bob, &[&bob]);
+        assert!(res.unwrap_err().contains("VaultDepositCapExceeded"));
+
+        let res = send(&mut program, &[deposit_ix(&fixture, &bob.pubkey(), 500)], &bob, &[&bob]);
+        assert!(res.is_ok(), "Deposit up to the vault cap should succeed: {:?}", res.err());
+        assert_eq!(get_vault(&program, &fixture.vault).token_reserve_amount, 1_000);
+    }
+
+    #[test]
+    fn test_locked_deposit() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+
+        let depositor = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        fund_user(&mut program, &payer, &fixture, &depositor.pubkey(), 1_000);
+
+        let res = send(
+            &mut program,
+            &[locked_deposit_ix(&fixture, &depositor.pubkey(), 600, crate::state::MAX_LOCK_DURATION + 1)],
+            &depositor,
+            &[&depositor],
+        );
+        assert!(res.unwrap_err().contains("InvalidLockDuration"));
+
+        let thirty_days: i64 = 30 * 86_400;
+        let res = send(
+            &mut program,
+            &[locked_deposit_ix(&fixture, &depositor.pubkey(), 600, thirty_days)],
+            &depositor,
+            &[&depositor],
+        );
+        assert!(res.is_ok(), "Locked deposit should succeed: {:?}", res.err());
+
+        let registry_pda = registry_address(&fixture, &depositor.pubkey());
+        let locked_until = get_registry(&program, &registry_pda).locked_until;
+        assert_eq!(locked_until, program.get_sysvar::<Clock>().unix_timestamp + thirty_days);
+
+        // A shorter lock on top does not bring maturity forward
+        let res = send(&mut program, &[deposit_ix(&fixture, &depositor.pubkey(), 100)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Unlocked top-up should succeed: {:?}", res.err());
+        assert_eq!(get_registry(&program, &registry_pda).locked_until, locked_until);
+
+        let withdraw_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: withdraw_accounts(&fixture, &depositor.pubkey()),
+            data: crate::instruction::Withdraw { amount: 100, max_fee: 0 }.data(),
+        };
+        let res = send(&mut program, std::slice::from_ref(&withdraw_ix), &depositor, &[&depositor]);
+        assert!(res.unwrap_err().contains("PositionLocked"));
+
+        let mut clock = program.get_sysvar::<Clock>();
+        clock.unix_timestamp = locked_until;
+        program.set_sysvar::<Clock>(&clock);
+
+        program.expire_blockhash();
+        let res = send(&mut program, &[withdraw_ix], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Withdraw after maturity should succeed: {:?}", res.err());
+    }
+
+    #[test]
+    fn test_withdrawal_queue() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+        let mint = fixture.mint.pubkey();
+
+        let depositor = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let depositor_ata = fund_user(&mut program, &payer, &fixture, &depositor.pubkey(), 1_000);
+        let res = send(&mut program, &[deposit_ix(&fixture, &depositor.pubkey(), 1_000)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+
+        let set_cooldown_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::VaultAdmin {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::SetWithdrawalCooldown { cooldown: 3_600 }.data(),
+        };
+        let res = send(&mut program, &[set_cooldown_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Setting the cooldown should succeed: {:?}", res.err());
+
+        let withdrawal_request = |id: u64| {
+            Pubkey::find_program_address(
+                &[b"withdrawal_request", fixture.vault.as_ref(), &id.to_le_bytes()],
+                &PROGRAM_ID,
+            )
+            .0
+        };
+
+        let request_ix = |id: u64, shares: u64| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::RequestWithdraw {
+                withdrawer: depositor.pubkey(),
+                vault: fixture.vault,
+                vault_registry_entry: registry_address(&fixture, &depositor.pubkey()),
+                withdrawal_request: withdrawal_request(id),
+                mint,
+                vault_token_reserve: fixture.reserve,
+                share_mint: fixture.share_mint,
+                withdrawer_share_account: share_account_address(&fixture, &depositor.pubkey()),
+                token_program: TOKEN_PROGRAM_ID,
+                share_token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::RequestWithdraw { shares }.data(),
+        };
+        let fulfil_ix = |ids: &[u64]| {
+            let mut accounts = crate::accounts::FulfilWithdrawals {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+                vault_token_reserve: fixture.reserve,
+            }
+            .to_account_metas(None);
+            accounts.extend(
+                ids.iter()
+                    .map(|id| anchor_lang::prelude::AccountMeta::new(withdrawal_request(*id), false)),
+            );
+            Instruction {
+                program_id: PROGRAM_ID,
+                accounts,
+                data: crate::instruction::FulfilWithdrawals { count: ids.len() as u8 }.data(),
+            }
+        };
+        let claim_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::ClaimWithdraw {
+                withdrawer: depositor.pubkey(),
+                vault: fixture.vault,
+                withdrawal_request: withdrawal_request(0),
+                mint,
+                withdrawer_token_account: depositor_ata,
+                vault_token_reserve: fixture.reserve,
+                extra_account_meta_list: Some(fixture.extra_account_meta_list),
+                transfer_hook_program: Some(transfer_hook::ID),
+                vault_whitelist: Some(fixture.vault_whitelist),
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::ClaimWithdraw { request_id: 0, max_fee: 0 }.data(),
+        };
+
+        // Every request gets its own account, there is no queue to fill up
+        let res = send(&mut program, &[request_ix(0, 600), request_ix(1, 100)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Withdrawal requests should succeed: {:?}", res.err());
+
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.token_reserve_amount, 300);
+        assert_eq!(vault.queued_withdrawals, 700);
+        assert_eq!(get_registry(&program, &registry_address(&fixture, &depositor.pubkey())).shares, 300);
+
+        let res = send(&mut program, std::slice::from_ref(&claim_ix), &depositor, &[&depositor]);
+        assert!(res.unwrap_err().contains("WithdrawalNotFulfilled"));
+
+        // Requests are fulfilled oldest first
+        let res = send(&mut program, &[fulfil_ix(&[1])], &payer, &[&payer]);
+        assert!(res.unwrap_err().contains("WithdrawalRequestOutOfOrder"));
+
+        let res = send(&mut program, &[fulfil_ix(&[0, 1])], &payer, &[&payer]);
+        assert!(res.is_ok(), "Fulfilling should succeed: {:?}", res.err());
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.fulfilled_withdrawals, 700);
+        assert_eq!(vault.next_withdrawal_to_fulfil, 2);
+
+        program.expire_blockhash();
+        let res = send(&mut program, std::slice::from_ref(&claim_ix), &depositor, &[&depositor]);
+        assert!(res.unwrap_err().contains("WithdrawalCooldown"));
+
+        let mut clock = program.get_sysvar::<Clock>();
+        clock.unix_timestamp += 3_600;
+        program.set_sysvar::<Clock>(&clock);
+
+        program.expire_blockhash();
+        let res = send(&mut program, &[claim_ix], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Claim after the cooldown should succeed: {:?}", res.err());
+
+        assert_eq!(token_balance(&program, &depositor_ata), 600);
+        assert!(program.get_account(&withdrawal_request(0)).is_none_or(|account| account.lamports == 0));
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.queued_withdrawals, 100);
+        assert_eq!(vault.fulfilled_withdrawals, 100);
+        assert_eq!(vault.token_reserve_amount, 300);
+    }
+
+    #[test]
+    fn test_fees() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+        let mint = fixture.mint.pubkey();
+
+        let depositor = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let depositor_ata = fund_user(&mut program, &payer, &fixture, &depositor.pubkey(), 1_000);
+        let fee_recipient = create_ata(&mut program, &payer, &payer.pubkey(), &mint);
+
+        let admin_ix = |data: Vec<u8>| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::VaultAdmin {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+            }
+            .to_account_metas(None),
+            data,
+        };
+        let set_fees_ix = |deposit_fee_bps: u16| {
+            admin_ix(crate::instruction::SetFees {
+                deposit_fee_bps,
+                withdraw_fee_bps: 200,
+                performance_fee_bps: 1_000,
+            }
+            .data())
+        };
+        let res = send(&mut program, &[set_fees_ix(crate::utils::MAX_FEE_BPS + 1)], &payer, &[&payer]);
+        assert!(res.unwrap_err().contains("InvalidFee"));
+
+        // Raised fees wait out the rate change delay
+        let res = send(
+            &mut program,
+            &[set_fees_ix(100), admin_ix(crate::instruction::SetFeeRecipient { fee_recipient }.data())],
+            &payer,
+            &[&payer],
+        );
+        assert!(res.is_ok(), "Setting fees should succeed: {:?}", res.err());
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.deposit_fee_bps, 0);
+        assert_eq!(vault.pending_deposit_fee_bps, 100);
+        let res = send(&mut program, &[admin_ix(crate::instruction::ApplyFees {}.data())], &payer, &[&payer]);
+        assert!(res.unwrap_err().contains("FeeIncreaseTimelocked"));
+
+        let mut clock = program.get_sysvar::<Clock>();
+        clock.unix_timestamp = vault.pending_fees_at;
+        program.set_sysvar::<Clock>(&clock);
+        program.expire_blockhash();
+        let res = send(&mut program, &[admin_ix(crate::instruction::ApplyFees {}.data())], &payer, &[&payer]);
+        assert!(res.is_ok(), "Applying fees should succeed: {:?}", res.err());
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.deposit_fee_bps, 100);
+        assert_eq!(vault.withdraw_fee_bps, 200);
+        assert_eq!(vault.performance_fee_bps, 1_000);
+        assert_eq!(vault.pending_fees_at, 0);
+
+        let res = send(&mut program, &[deposit_ix(&fixture, &depositor.pubkey(), 1_000)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.token_reserve_amount, 990);
+        assert_eq!(vault.accrued_fees, 10);
+        assert_eq!(get_registry(&program, &registry_address(&fixture, &depositor.pubkey())).shares, 990);
+
+        let redeem_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: withdraw_accounts(&fixture, &depositor.pubkey()),
+            data: crate::instruction::Redeem { shares: 990, max_fee: 0 }.data(),
+        };
+        let res = send(&mut program, &[redeem_ix], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Redeem should succeed: {:?}", res.err());
+
+        // 2% of 990, rounded up
+        assert_eq!(token_balance(&program, &depositor_ata), 970);
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.token_reserve_amount, 0);
+        assert_eq!(vault.accrued_fees, 30);
+
+        let collect_fees_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::CollectFees {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+                mint,
+                fee_recipient,
+                vault_token_reserve: fixture.reserve,
+                extra_account_meta_list: Some(fixture.extra_account_meta_list),
+                transfer_hook_program: Some(transfer_hook::ID),
+                vault_whitelist: Some(fixture.vault_whitelist),
+                token_program: TOKEN_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::CollectFees {}.data(),
+        };
+        let res = send(&mut program, &[collect_fees_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Collecting fees should succeed: {:?}", res.err());
+
+        assert_eq!(token_balance(&program, &fee_recipient), 30);
+        assert_eq!(get_vault(&program, &fixture.vault).accrued_fees, 0);
+        assert_eq!(token_balance(&program, &fixture.reserve), 0);
+    }
+
+    #[test]
+    fn test_close_position_and_vault() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+        let mint = fixture.mint.pubkey();
+
+        let depositor = Keypair::new();
+        let other = Keypair::new();
+        for user in [&depositor, &other] {
+            program.airdrop(&user.pubkey(), LAMPORTS_PER_SOL).unwrap();
+            fund_user(&mut program, &payer, &fixture, &user.pubkey(), 1_000);
+        }
+        let authority_ata = create_ata(&mut program, &payer, &payer.pubkey(), &mint);
+
+        let res = send(&mut program, &[deposit_ix(&fixture, &depositor.pubkey(), 600)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+        assert_eq!(get_vault(&program, &fixture.vault).open_positions, 1);
+
+        // A second position is emptied but left open
+        let res = send(&mut program, &[deposit_ix(&fixture, &other.pubkey(), 400)], &other, &[&other]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+        let redeem_other_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: withdraw_accounts(&fixture, &other.pubkey()),
+            data: crate::instruction::Redeem { shares: 400, max_fee: 0 }.data(),
+        };
+        let res = send(&mut program, &[redeem_other_ix], &other, &[&other]);
+        assert!(res.is_ok(), "Redeem should succeed: {:?}", res.err());
+
+        let registry_pda = registry_address(&fixture, &depositor.pubkey());
+        let close_position_ix = |user: &Pubkey| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::ClosePosition {
+                user: *user,
+                vault: fixture.vault,
+                vault_registry_entry: registry_address(&fixture, user),
+            }
+            .to_account_metas(None),
+            data: crate::instruction::ClosePosition {}.data(),
+        };
+        let close_vault_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::CloseVault {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+                rate_history: None,
+                pending_rate_change: None,
+                mint,
+                share_mint: fixture.share_mint,
+                authority_token_account: authority_ata,
+                vault_token_reserve: fixture.reserve,
+                extra_account_meta_list: Some(fixture.extra_account_meta_list),
+                transfer_hook_program: Some(transfer_hook::ID),
+                vault_whitelist: Some(fixture.vault_whitelist),
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::CloseVault {}.data(),
+        };
+
+        let res = send(&mut program, &[close_position_ix(&depositor.pubkey())], &depositor, &[&depositor]);
+        assert!(res.unwrap_err().contains("PositionNotEmpty"));
+        let res = send(&mut program, std::slice::from_ref(&close_vault_ix), &payer, &[&payer]);
+        assert!(res.unwrap_err().contains("VaultNotEmpty"));
+
+        let redeem_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: withdraw_accounts(&fixture, &depositor.pubkey()),
+            data: crate::instruction::Redeem { shares: 600, max_fee: 0 }.data(),
+        };
+        let res = send(&mut program, &[redeem_ix], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Redeem should succeed: {:?}", res.err());
+
+        let lamports_before = program.get_balance(&depositor.pubkey()).unwrap();
+        program.expire_blockhash();
+        let res = send(&mut program, &[close_position_ix(&depositor.pubkey())], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Closing an empty position should succeed: {:?}", res.err());
+        assert!(program.get_account(&registry_pda).is_none_or(|a| a.data.is_empty()));
+        assert!(program.get_balance(&depositor.pubkey()).unwrap() > lamports_before);
+        assert_eq!(get_vault(&program, &fixture.vault).open_positions, 1);
+
+        // Positions left open do not keep the vault from closing
+        program.expire_blockhash();
+        let res = send(&mut program, &[close_vault_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Closing an empty vault should succeed: {:?}", res.err());
+        assert!(program.get_account(&fixture.vault).is_none_or(|a| a.data.is_empty()));
+        assert!(program.get_account(&fixture.reserve).is_none_or(|a| a.data.is_empty()));
+
+        // and can still be closed by their owners afterwards
+        let res = send(&mut program, &[close_position_ix(&other.pubkey())], &other, &[&other]);
+        assert!(res.is_ok(), "Closing a position of a closed vault should succeed: {:?}", res.err());
+        assert!(program.get_account(&registry_address(&fixture, &other.pubkey())).is_none_or(|a| a.data.is_empty()));
+
+        // The share mint outlives the vault, so its index is not reused
+        let (init_vault_ix, _, _, _) = initialize_vault_ix(&payer.pubkey(), &mint, 0);
+        program.expire_blockhash();
+        let res = send(&mut program, &[init_vault_ix], &payer, &[&payer]);
+        assert!(res.is_err(), "A closed vault's index should not be reusable");
+    }
+
+    #[test]
+    fn test_close_vault_with_transfer_fee_mint() {
+        use anchor_spl::token_2022::spl_token_2022::extension::transfer_fee::{TransferFeeAmount, TransferFeeConfig};
+
+        let (mut program, payer) = setup();
+        // 1% transfer fee
+        let fixture = setup_hookless_vault(&mut program, &payer, Some(100));
+        let mint = fixture.mint.pubkey();
+
+        let depositor = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let depositor_ata = create_ata(&mut program, &payer, &depositor.pubkey(), &mint);
+        mint_tokens_to(&mut program, &mint, &depositor_ata, &payer, 1_000);
+        let authority_ata = create_ata(&mut program, &payer, &payer.pubkey(), &mint);
+
+        let res = send(&mut program, &[hookless_deposit_ix(&fixture, &depositor.pubkey(), 1_000, 10)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+        let redeem_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: hookless_withdraw_ix(&fixture, &depositor.pubkey(), 0, 0).accounts,
+            data: crate::instruction::Redeem { shares: 990, max_fee: 10 }.data(),
+        };
+        let res = send(&mut program, &[redeem_ix], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Redeem should succeed: {:?}", res.err());
+
+        // The reserve is empty but still holds the fee withheld from the deposit
+        let reserve_account = program.get_account(&fixture.reserve).unwrap();
+        let reserve_state = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&reserve_account.data).unwrap();
+        assert_eq!(reserve_state.base.amount, 0);
+        assert_eq!(u64::from(reserve_state.get_extension::<TransferFeeAmount>().unwrap().withheld_amount), 10);
+
+        let close_vault_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::CloseVault {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+                rate_history: None,
+                pending_rate_change: None,
+                mint,
+                share_mint: fixture.share_mint,
+                authority_token_account: authority_ata,
+                vault_token_reserve: fixture.reserve,
+                extra_account_meta_list: None,
+                transfer_hook_program: None,
+                vault_whitelist: None,
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::CloseVault {}.data(),
+        };
+        let res = send(&mut program, &[close_vault_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Closing a vault with withheld fees should succeed: {:?}", res.err());
+        assert!(program.get_account(&fixture.vault).is_none_or(|a| a.data.is_empty()));
+        assert!(program.get_account(&fixture.reserve).is_none_or(|a| a.data.is_empty()));
+
+        // The withheld fees moved to the mint
+        let mint_account = program.get_account(&mint).unwrap();
+        let mint_state = StateWithExtensions::<Token2022Mint>::unpack(&mint_account.data).unwrap();
+        assert_eq!(u64::from(mint_state.get_extension::<TransferFeeConfig>().unwrap().withheld_amount), 10);
+    }
+
+    #[test]
+    fn test_authority_transfer() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+
+        let new_authority = Keypair::new();
+        let stranger = Keypair::new();
+        program.airdrop(&new_authority.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        program.airdrop(&stranger.pubkey(), LAMPORTS_PER_SOL).unwrap();
+
+        let admin_ix = |authority: &Pubkey, data: Vec<u8>| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::VaultAdmin {
+                vault_authority: *authority,
+                vault: fixture.vault,
+                vault_roles: None,
+            }
+            .to_account_metas(None),
+            data,
+        };
+        let accept_ix = |signer: &Pubkey| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::AcceptAuthority {
+                new_authority: *signer,
+                vault: fixture.vault,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::AcceptAuthority {}.data(),
+        };
+
+        let propose = crate::instruction::ProposeAuthority { new_authority: new_authority.pubkey() }.data();
+        let res = send(&mut program, &[admin_ix(&new_authority.pubkey(), propose.clone())], &new_authority, &[&new_authority]);
+        assert!(res.is_err(), "Only the current authority can propose a new one");
+
+        let res = send(&mut program, &[admin_ix(&payer.pubkey(), propose)], &payer, &[&payer]);
+        assert!(res.is_ok(), "Proposing a new authority should succeed: {:?}", res.err());
+        assert_eq!(get_vault(&program, &fixture.vault).pending_authority, new_authority.pubkey());
+
+        let res = send(&mut program, &[accept_ix(&stranger.pubkey())], &stranger, &[&stranger]);
+        assert!(res.unwrap_err().contains("Unauthorized"));
+
+        let res = send(&mut program, &[accept_ix(&new_authority.pubkey())], &new_authority, &[&new_authority]);
+        assert!(res.is_ok(), "Accepting the authority should succeed: {:?}", res.err());
+
+        // The vault keeps its address, only the controlling key changes
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.vault_authority, new_authority.pubkey());
+        assert_eq!(vault.creator, payer.pubkey());
+        assert_eq!(vault.pending_authority, Pubkey::default());
+
+        let set_cooldown = crate::instruction::SetWithdrawalCooldown { cooldown: 60 }.data();
+        let res = send(&mut program, &[admin_ix(&payer.pubkey(), set_cooldown.clone())], &payer, &[&payer]);
+        assert!(res.is_err(), "The previous authority should be locked out");
+        let res = send(&mut program, &[admin_ix(&new_authority.pubkey(), set_cooldown)], &new_authority, &[&new_authority]);
+        assert!(res.is_ok(), "The new authority should manage the vault: {:?}", res.err());
+
+        // Vault-signed operations still work with the original seeds
+        let depositor = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        fund_user(&mut program, &payer, &fixture, &depositor.pubkey(), 1_000);
+        let res = send(&mut program, &[deposit_ix(&fixture, &depositor.pubkey(), 600)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+    }
+
+    #[test]
+    fn test_multisig_authority() {
+        use anchor_lang::prelude::AccountMeta;
+
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+
+        let members = [Keypair::new(), Keypair::new(), Keypair::new()];
+        for member in &members {
+            program.airdrop(&member.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        }
+
+        let create_key = Keypair::new();
+        let (multisig, _) =
+            Pubkey::find_program_address(&[b"multisig", create_key.pubkey().as_ref()], &PROGRAM_ID);
+        let create_multisig_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::CreateMultisig {
+                payer: payer.pubkey(),
+                create_key: create_key.pubkey(),
+                multisig,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::CreateMultisig {
+                signers: members.iter().map(|m| m.pubkey()).collect(),
+                threshold: 2,
+            }
+            .data(),
+        };
+        let res = send(&mut program, &[create_multisig_ix], &payer, &[&payer, &create_key]);
+        assert!(res.is_ok(), "Creating the multisig should succeed: {:?}", res.err());
+
+        // Approval from the multisig: the multisig account, then co-signers
+        let with_co_signers = |mut accounts: Vec<AccountMeta>, co_signers: &[&Keypair]| {
+            accounts.push(AccountMeta::new_readonly(multisig, false));
+            for co_signer in co_signers {
+                accounts.push(AccountMeta::new_readonly(co_signer.pubkey(), true));
+            }
+            accounts
+        };
+        let admin_accounts = |authority: &Pubkey| {
+            crate::accounts::VaultAdmin {
+                vault_authority: *authority,
+                vault: fixture.vault,
+                vault_roles: None,
+            }
+            .to_account_metas(None)
+        };
+
+        let propose_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: admin_accounts(&payer.pubkey()),
+            data: crate::instruction::ProposeAuthority { new_authority: multisig }.data(),
+        };
+        let accept_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: with_co_signers(
+                crate::accounts::AcceptAuthority {
+                    new_authority: members[0].pubkey(),
+                    vault: fixture.vault,
+                }
+                .to_account_metas(None),
+                &[&members[1]],
+            ),
+            data: crate::instruction::AcceptAuthority {}.data(),
+        };
+        let res = send(&mut program, &[propose_ix, accept_ix], &payer, &[&payer, &members[0], &members[1]]);
+        assert!(res.is_ok(), "Multisig should accept the authority: {:?}", res.err());
+        assert_eq!(get_vault(&program, &fixture.vault).vault_authority, multisig);
+
+        let set_cooldown_ix = |co_signers: &[&Keypair]| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: with_co_signers(admin_accounts(&members[0].pubkey()), co_signers),
+            data: crate::instruction::SetWithdrawalCooldown { cooldown: 60 }.data(),
+        };
+
+        let res = send(&mut program, &[set_cooldown_ix(&[])], &members[0], &[&members[0]]);
+        assert!(res.unwrap_err().contains("MultisigThresholdNotMet"));
+
+        let res = send(&mut program, &[set_cooldown_ix(&[&members[0]])], &members[0], &[&members[0]]);
+        assert!(res.unwrap_err().contains("MultisigThresholdNotMet"), "A signer only counts once");
+
+        let res = send(
+            &mut program,
+            &[set_cooldown_ix(&[&members[2]])],
+            &members[0],
+            &[&members[0], &members[2]],
+        );
+        assert!(res.is_ok(), "2 of 3 signers should be enough: {:?}", res.err());
+        assert_eq!(get_vault(&program, &fixture.vault).withdrawal_cooldown, 60);
+
+        // Withdrawals the multisig approves are paid to the multisig, not to the submitting member
+        let depositor = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        fund_user(&mut program, &payer, &fixture, &depositor.pubkey(), 1_000);
+        let res = send(&mut program, &[deposit_ix(&fixture, &depositor.pubkey(), 600)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+
+        let multisig_ata = create_ata(&mut program, &payer, &multisig, &fixture.mint.pubkey());
+        let member_ata = create_ata(&mut program, &payer, &members[0].pubkey(), &fixture.mint.pubkey());
+        let admin_withdraw_ix = |authority_token_account: Pubkey| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: with_co_signers(
+                crate::accounts::AdminWithdraw {
+                    vault_authority: members[0].pubkey(),
+                    vault: fixture.vault,
+                    mint: fixture.mint.pubkey(),
+                    authority_token_account,
+                    vault_token_reserve: fixture.reserve,
+                    extra_account_meta_list: Some(fixture.extra_account_meta_list),
+                    transfer_hook_program: Some(transfer_hook::ID),
+                    vault_whitelist: Some(fixture.vault_whitelist),
+                    associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                    token_program: TOKEN_PROGRAM_ID,
+                    system_program: SYSTEM_PROGRAM_ID,
+                }
+                .to_account_metas(None),
+                &[&members[2]],
+            ),
+            data: crate::instruction::AdminWithdraw { amount: 200 }.data(),
+        };
+
+        let res = send(&mut program, &[admin_withdraw_ix(member_ata)], &members[0], &[&members[0], &members[2]]);
+        assert!(res.is_err(), "A member should not receive an approved withdrawal");
+
+        let res = send(&mut program, &[admin_withdraw_ix(multisig_ata)], &members[0], &[&members[0], &members[2]]);
+        assert!(res.is_ok(), "Admin withdraw should succeed: {:?}", res.err());
+        assert_eq!(token_balance(&program, &multisig_ata), 200);
+        assert_eq!(token_balance(&program, &member_ata), 0);
+
+        let old_authority_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: admin_accounts(&payer.pubkey()),
+            data: crate::instruction::SetWithdrawalCooldown { cooldown: 0 }.data(),
+        };
+        let res = send(&mut program, &[old_authority_ix], &payer, &[&payer]);
+        assert!(res.is_err(), "The previous single-key authority should be locked out");
+    }
+
+    #[test]
+    fn test_multisig_key_cannot_sign_alone() {
+        use anchor_lang::{AccountSerialize, Space};
+
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+
+        // A multisig living at a keypair address, whose key could otherwise sign for it
+        let multisig = Keypair::new();
+        let members = [Keypair::new(), Keypair::new()];
+        let mut data = Vec::new();
+        crate::state::Multisig {
+            signers: members.iter().map(|m| m.pubkey()).collect(),
+            threshold: 2,
+            create_key: multisig.pubkey(),
+            bump: 0,
+        }
+        .try_serialize(&mut data)
+        .unwrap();
+        data.resize(8 + crate::state::Multisig::INIT_SPACE, 0);
+        let lamports = program.minimum_balance_for_rent_exemption(data.len());
+        program
+            .set_account(
+                multisig.pubkey(),
+                Account { lamports, data, owner: PROGRAM_ID, executable: false, rent_epoch: 0 },
+            )
+            .unwrap();
+
+        let propose_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::VaultAdmin {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::ProposeAuthority { new_authority: multisig.pubkey() }.data(),
+        };
+        let accept_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::AcceptAuthority {
+                new_authority: multisig.pubkey(),
+                vault: fixture.vault,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::AcceptAuthority {}.data(),
+        };
+        let res = send(&mut program, &[propose_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Proposal should succeed: {:?}", res.err());
+
+        let res = send(&mut program, &[accept_ix], &payer, &[&payer, &multisig]);
+        assert!(
+            res.unwrap_err().contains("Unauthorized"),
+            "The multisig key alone should not approve for the multisig"
+        );
+        assert_eq!(get_vault(&program, &fixture.vault).vault_authority, payer.pubkey());
+    }
+
+    #[test]
+    fn test_vault_roles() {
+        use crate::state::Role;
+
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+
+        let operator = Keypair::new();
+        program.airdrop(&operator.pubkey(), LAMPORTS_PER_SOL).unwrap();
+
+        let roles = Pubkey::find_program_address(&[b"vault_roles", fixture.vault.as_ref()], &PROGRAM_ID).0;
+        let manage_ix = |signer: &Pubkey, grant: bool, role: Role| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::ManageRoles {
+                vault_authority: *signer,
+                vault: fixture.vault,
+                vault_roles: roles,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: if grant {
+                crate::instruction::GrantRole { role, member: operator.pubkey() }.data()
+            } else {
+                crate::instruction::RevokeRole { role, member: operator.pubkey() }.data()
+            },
+        };
+        let admin_ix = |data: Vec<u8>| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::VaultAdmin {
+                vault_authority: operator.pubkey(),
+                vault: fixture.vault,
+                vault_roles: Some(roles),
+            }
+            .to_account_metas(None),
+            data,
+        };
+
+        let res = send(&mut program, &[manage_ix(&operator.pubkey(), true, Role::Admin)], &operator, &[&operator]);
+        assert!(res.is_err(), "Only the vault authority can grant roles");
+
+        let res = send(&mut program, &[manage_ix(&payer.pubkey(), true, Role::Pauser)], &payer, &[&payer]);
+        assert!(res.is_ok(), "Granting a role should succeed: {:?}", res.err());
+
+        // A pauser is not an admin, and the error names the missing role
+        let set_cooldown = crate::instruction::SetWithdrawalCooldown { cooldown: 60 }.data();
+        let res = send(&mut program, &[admin_ix(set_cooldown.clone())], &operator, &[&operator]);
+        assert!(res.unwrap_err().contains("signer lacks the admin role"));
+
+        let res = send(&mut program, &[manage_ix(&payer.pubkey(), true, Role::Admin)], &payer, &[&payer]);
+        assert!(res.is_ok(), "Granting a second role should succeed: {:?}", res.err());
+        let res = send(&mut program, &[admin_ix(set_cooldown.clone())], &operator, &[&operator]);
+        assert!(res.is_ok(), "Admins should manage vault settings: {:?}", res.err());
+        assert_eq!(get_vault(&program, &fixture.vault).withdrawal_cooldown, 60);
+
+        // Moving funds out of the reserve stays with the vault authority
+        let authority_ata = create_ata(&mut program, &payer, &payer.pubkey(), &fixture.mint.pubkey());
+        let admin_withdraw_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::AdminWithdraw {
+                vault_authority: operator.pubkey(),
+                vault: fixture.vault,
+                mint: fixture.mint.pubkey(),
+                authority_token_account: authority_ata,
+                vault_token_reserve: fixture.reserve,
+                extra_account_meta_list: Some(fixture.extra_account_meta_list),
+                transfer_hook_program: Some(transfer_hook::ID),
+                vault_whitelist: Some(fixture.vault_whitelist),
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::AdminWithdraw { amount: 1 }.data(),
+        };
+        let res = send(&mut program, &[admin_withdraw_ix], &operator, &[&operator]);
+        assert!(res.unwrap_err().contains("Unauthorized"), "Admins should not withdraw from the reserve");
+
+        let vault_roles: crate::state::VaultRoles = {
+            let account = program.get_account(&roles).unwrap();
+            anchor_lang::AccountDeserialize::try_deserialize(&mut account.data.as_slice()).unwrap()
+        };
+        assert!(vault_roles.has_role(&operator.pubkey(), Role::Admin));
+        assert!(vault_roles.has_role(&operator.pubkey(), Role::Pauser));
+        assert!(!vault_roles.has_role(&operator.pubkey(), Role::FeeCollector));
+
+        let res = send(&mut program, &[manage_ix(&payer.pubkey(), false, Role::Admin)], &payer, &[&payer]);
+        assert!(res.is_ok(), "Revoking a role should succeed: {:?}", res.err());
+        program.expire_blockhash();
+        let res = send(&mut program, &[admin_ix(set_cooldown)], &operator, &[&operator]);
+        assert!(res.unwrap_err().contains("signer lacks the admin role"));
+    }
+
+    #[test]
+    fn test_deposit_and_withdraw_events() {
+        use anchor_lang::{AnchorDeserialize, Discriminator};
+        use crate::events::{DepositEvent, WithdrawEvent};
+
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+
+        let depositor = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        fund_user(&mut program, &payer, &fixture, &depositor.pubkey(), 1_000);
+
+        // Events are the data of self-CPIs signed by the event authority
+        fn events<T: AnchorDeserialize + Discriminator>(meta: &litesvm::types::TransactionMetadata) -> Vec<T> {
+            let prefix = [anchor_lang::event::EVENT_IX_TAG_LE, T::DISCRIMINATOR].concat();
+            meta.inner_instructions
+                .iter()
+                .flatten()
+                .filter_map(|inner| inner.instruction.data.strip_prefix(prefix.as_slice()))
+                .map(|mut data| T::deserialize(&mut data).unwrap())
+                .collect()
+        }
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[deposit_ix(&fixture, &depositor.pubkey(), 600)],
+            Some(&depositor.pubkey()),
+            &[&depositor],
+            program.latest_blockhash(),
+        );
+        let meta = program.send_transaction(transaction).expect("Deposit should succeed");
+        let deposits = events::<DepositEvent>(&meta);
+        assert_eq!(deposits.len(), 1);
+        assert_eq!(deposits[0].vault, fixture.vault);
+        assert_eq!(deposits[0].depositor, depositor.pubkey());
+        assert_eq!(deposits[0].payer, depositor.pubkey());
+        assert_eq!(deposits[0].amount, 600);
+        assert_eq!(deposits[0].shares_minted, 600);
+        assert_eq!(deposits[0].user_balance_after, 600);
+        assert_eq!(deposits[0].vault_balance_after, 600);
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[Instruction {
+                program_id: PROGRAM_ID,
+                accounts: withdraw_accounts(&fixture, &depositor.pubkey()),
+                data: crate::instruction::Withdraw { amount: 200, max_fee: 0 }.data(),
+            }],
+            Some(&depositor.pubkey()),
+            &[&depositor],
+            program.latest_blockhash(),
+        );
+        let meta = program.send_transaction(transaction).expect("Withdraw should succeed");
+        let withdrawals = events::<WithdrawEvent>(&meta);
+        assert_eq!(withdrawals.len(), 1);
+        assert_eq!(withdrawals[0].withdrawer, depositor.pubkey());
+        assert_eq!(withdrawals[0].amount, 200);
+        assert_eq!(withdrawals[0].user_shares_after, 400);
+        assert_eq!(withdrawals[0].vault_balance_after, 400);
+    }
+
+    #[test]
+    fn test_unique_depositor_count() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+
+        let alice = Keypair::new();
+        let bob = Keypair::new();
+        for user in [&alice, &bob] {
+            program.airdrop(&user.pubkey(), LAMPORTS_PER_SOL).unwrap();
+            fund_user(&mut program, &payer, &fixture, &user.pubkey(), 1_000);
+        }
+
+        let res = send(&mut program, &[deposit_ix(&fixture, &alice.pubkey(), 300)], &alice, &[&alice]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+        program.expire_blockhash();
+        let res = send(&mut program, &[deposit_ix(&fixture, &alice.pubkey(), 300)], &alice, &[&alice]);
+        assert!(res.is_ok(), "Second deposit should succeed: {:?}", res.err());
+        let res = send(&mut program, &[deposit_ix(&fixture, &bob.pubkey(), 200)], &bob, &[&bob]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+
+        // Repeat deposits by the same user do not count as new depositors
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.num_depositors, 2);
+        assert_eq!(vault.num_deposits, 3);
+        assert_eq!(vault.num_withdrawals, 0);
+
+        let redeem_ix = |user: &Pubkey, shares: u64| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: withdraw_accounts(&fixture, user),
+            data: crate::instruction::Redeem { shares, max_fee: 0 }.data(),
+        };
+        let res = send(&mut program, &[redeem_ix(&alice.pubkey(), 100)], &alice, &[&alice]);
+        assert!(res.is_ok(), "Partial redeem should succeed: {:?}", res.err());
+        assert_eq!(get_vault(&program, &fixture.vault).num_depositors, 2);
+
+        let res = send(&mut program, &[redeem_ix(&alice.pubkey(), 500)], &alice, &[&alice]);
+        assert!(res.is_ok(), "Full redeem should succeed: {:?}", res.err());
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.num_depositors, 1, "An emptied position is no longer an active depositor");
+        assert_eq!(vault.num_withdrawals, 2);
+
+        // Coming back counts as an active depositor again
+        program.expire_blockhash();
+        let res = send(&mut program, &[deposit_ix(&fixture, &alice.pubkey(), 100)], &alice, &[&alice]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.num_depositors, 2);
+        assert_eq!(vault.num_deposits, 4);
+    }
+
+    #[test]
+    fn test_sync_reserve() {
+        use crate::state::SurplusPolicy;
+
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+
+        let depositor = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        fund_user(&mut program, &payer, &fixture, &depositor.pubkey(), 1_000);
+        let res = send(&mut program, &[deposit_ix(&fixture, &depositor.pubkey(), 600)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+
+        let sync_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::SyncReserve {
+                vault: fixture.vault,
+                mint: fixture.mint.pubkey(),
+                vault_token_reserve: fixture.reserve,
+                token_program: TOKEN_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::SyncReserve {}.data(),
+        };
+
+        // Tokens sent straight to the reserve are yield by default, anyone can sync
+        mint_tokens_to(&mut program, &fixture.mint.pubkey(), &fixture.reserve, &payer, 100);
+        let res = send(&mut program, std::slice::from_ref(&sync_ix), &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Syncing the reserve should succeed: {:?}", res.err());
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.token_reserve_amount, 700);
+        assert_eq!(vault.excess_reserve, 0);
+
+        let set_policy_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::VaultAdmin {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::SetSurplusPolicy { surplus_policy: SurplusPolicy::Recoverable }.data(),
+        };
+        let res = send(&mut program, &[set_policy_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Setting the surplus policy should succeed: {:?}", res.err());
+
+        mint_tokens_to(&mut program, &fixture.mint.pubkey(), &fixture.reserve, &payer, 50);
+        program.expire_blockhash();
+        let res = send(&mut program, &[sync_ix], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Syncing the reserve should succeed: {:?}", res.err());
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.token_reserve_amount, 700, "Recoverable surplus does not back shares");
+        assert_eq!(vault.excess_reserve, 50);
+
+        let authority_ata = create_ata(&mut program, &payer, &payer.pubkey(), &fixture.mint.pubkey());
+        let recover_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::AdminWithdraw {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                mint: fixture.mint.pubkey(),
+                authority_token_account: authority_ata,
+                vault_token_reserve: fixture.reserve,
+                extra_account_meta_list: Some(fixture.extra_account_meta_list),
+                transfer_hook_program: Some(transfer_hook::ID),
+                vault_whitelist: Some(fixture.vault_whitelist),
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::RecoverExcess {}.data(),
+        };
+        let res = send(&mut program, std::slice::from_ref(&recover_ix), &payer, &[&payer]);
+        assert!(res.is_ok(), "Recovering the excess should succeed: {:?}", res.err());
+        assert_eq!(token_balance(&program, &authority_ata), 50);
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.excess_reserve, 0);
+        assert_eq!(vault.token_reserve_amount, 700);
+        assert_eq!(token_balance(&program, &fixture.reserve), 700);
+
+        program.expire_blockhash();
+        let res = send(&mut program, &[recover_ix], &payer, &[&payer]);
+        assert!(res.unwrap_err().contains("NoExcessReserve"));
+    }
+
+    #[test]
+    fn test_emergency_shutdown() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+
+        let alice = Keypair::new();
+        let bob = Keypair::new();
+        for user in [&alice, &bob] {
+            program.airdrop(&user.pubkey(), LAMPORTS_PER_SOL).unwrap();
+            fund_user(&mut program, &payer, &fixture, &user.pubkey(), 1_000);
+        }
+        let alice_ata = associated_token::get_associated_token_address_with_program_id(
+            &alice.pubkey(),
+            &fixture.mint.pubkey(),
+            &TOKEN_PROGRAM_ID,
+        );
+        let bob_ata = associated_token::get_associated_token_address_with_program_id(
+            &bob.pubkey(),
+            &fixture.mint.pubkey(),
+            &TOKEN_PROGRAM_ID,
+        );
+
+        let res = send(&mut program, &[locked_deposit_ix(&fixture, &alice.pubkey(), 600, 1_000_000)], &alice, &[&alice]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+        let res = send(&mut program, &[deposit_ix(&fixture, &bob.pubkey(), 400)], &bob, &[&bob]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+
+        let authority_ata = create_ata(&mut program, &payer, &payer.pubkey(), &fixture.mint.pubkey());
+        let admin_withdraw_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::AdminWithdraw {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                mint: fixture.mint.pubkey(),
+                authority_token_account: authority_ata,
+                vault_token_reserve: fixture.reserve,
+                extra_account_meta_list: Some(fixture.extra_account_meta_list),
+                transfer_hook_program: Some(transfer_hook::ID),
+                vault_whitelist: Some(fixture.vault_whitelist),
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::AdminWithdraw { amount: 200 }.data(),
+        };
+        let res = send(&mut program, std::slice::from_ref(&admin_withdraw_ix), &payer, &[&payer]);
+        assert!(res.is_ok(), "Admin withdraw should succeed: {:?}", res.err());
+
+        // Bob queues 100 of his shares, worth 80 tokens, before the shutdown
+        let withdrawal_request = |id: u64| {
+            Pubkey::find_program_address(
+                &[b"withdrawal_request", fixture.vault.as_ref(), &id.to_le_bytes()],
+                &PROGRAM_ID,
+            )
+            .0
+        };
+        let request_ix = |id: u64| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::RequestWithdraw {
+                withdrawer: bob.pubkey(),
+                vault: fixture.vault,
+                vault_registry_entry: registry_address(&fixture, &bob.pubkey()),
+                withdrawal_request: withdrawal_request(id),
+                mint: fixture.mint.pubkey(),
+                vault_token_reserve: fixture.reserve,
+                share_mint: fixture.share_mint,
+                withdrawer_share_account: share_account_address(&fixture, &bob.pubkey()),
+                token_program: TOKEN_PROGRAM_ID,
+                share_token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::RequestWithdraw { shares: 100 }.data(),
+        };
+        let res = send(&mut program, &[request_ix(0)], &bob, &[&bob]);
+        assert!(res.is_ok(), "Withdrawal request should succeed: {:?}", res.err());
+        assert_eq!(get_vault(&program, &fixture.vault).queued_withdrawals, 80);
+
+        let emergency_withdraw_ix = |user: &Pubkey| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::EmergencyWithdraw {
+                withdrawer: *user,
+                vault: fixture.vault,
+                vault_registry_entry: registry_address(&fixture, user),
+                mint: fixture.mint.pubkey(),
+                withdrawer_token_account: associated_token::get_associated_token_address_with_program_id(
+                    user,
+                    &fixture.mint.pubkey(),
+                    &TOKEN_PROGRAM_ID,
+                ),
+                vault_token_reserve: fixture.reserve,
+                share_mint: fixture.share_mint,
+                withdrawer_share_account: share_account_address(&fixture, user),
+                extra_account_meta_list: Some(fixture.extra_account_meta_list),
+                transfer_hook_program: Some(transfer_hook::ID),
+                vault_whitelist: Some(fixture.vault_whitelist),
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                share_token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+                event_authority: event_authority(),
+                program: PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::EmergencyWithdraw { max_fee: 0 }.data(),
+        };
+
+        let res = send(&mut program, &[emergency_withdraw_ix(&alice.pubkey())], &alice, &[&alice]);
+        assert!(res.unwrap_err().contains("VaultNotShutdown"));
+
+        let shutdown_ix = |authority: &Pubkey| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::VaultAdmin {
+                vault_authority: *authority,
+                vault: fixture.vault,
+                vault_roles: None,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::EmergencyShutdown {}.data(),
+        };
+        let res = send(&mut program, &[shutdown_ix(&alice.pubkey())], &alice, &[&alice]);
+        assert!(res.is_err(), "Only the vault authority can shut the vault down");
+        let res = send(&mut program, &[shutdown_ix(&payer.pubkey())], &payer, &[&payer]);
+        assert!(res.is_ok(), "Shutting the vault down should succeed: {:?}", res.err());
+        assert!(get_vault(&program, &fixture.vault).emergency_shutdown);
+
+        program.expire_blockhash();
+        let res = send(&mut program, &[deposit_ix(&fixture, &bob.pubkey(), 100)], &bob, &[&bob]);
+        assert!(res.unwrap_err().contains("VaultShutdown"));
+        let res = send(&mut program, &[admin_withdraw_ix], &payer, &[&payer]);
+        assert!(res.unwrap_err().contains("VaultShutdown"));
+
+        // Regular exits are closed, so nobody can leave in full ahead of the others
+        let withdraw_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: withdraw_accounts(&fixture, &bob.pubkey()),
+            data: crate::instruction::Withdraw { amount: 100, max_fee: 0 }.data(),
+        };
+        let res = send(&mut program, &[withdraw_ix], &bob, &[&bob]);
+        assert!(res.unwrap_err().contains("VaultShutdown"));
+        let res = send(&mut program, &[request_ix(1)], &bob, &[&bob]);
+        assert!(res.unwrap_err().contains("VaultShutdown"));
+        let claim_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::ClaimWithdraw {
+                withdrawer: bob.pubkey(),
+                vault: fixture.vault,
+                withdrawal_request: withdrawal_request(0),
+                mint: fixture.mint.pubkey(),
+                withdrawer_token_account: bob_ata,
+                vault_token_reserve: fixture.reserve,
+                extra_account_meta_list: Some(fixture.extra_account_meta_list),
+                transfer_hook_program: Some(transfer_hook::ID),
+                vault_whitelist: Some(fixture.vault_whitelist),
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::ClaimWithdraw { request_id: 0, max_fee: 0 }.data(),
+        };
+        let res = send(&mut program, &[claim_ix], &bob, &[&bob]);
+        assert!(res.unwrap_err().contains("VaultShutdown"));
+
+        // Half of the 800 tokens the vault owes go missing from the reserve
+        let mut reserve_account = program.get_account(&fixture.reserve).unwrap();
+        {
+            let mut reserve_state =
+                StateWithExtensionsMut::<spl_token_2022::state::Account>::unpack(&mut reserve_account.data).unwrap();
+            reserve_state.base.amount = 400;
+            reserve_state.pack_base();
+        }
+        program.set_account(fixture.reserve, reserve_account).unwrap();
+
+        // Each holder leaves with half of what they are owed, locks do not apply
+        program.expire_blockhash();
+        let res = send(&mut program, &[emergency_withdraw_ix(&alice.pubkey())], &alice, &[&alice]);
+        assert!(res.is_ok(), "Emergency withdraw should succeed: {:?}", res.err());
+        assert_eq!(token_balance(&program, &alice_ata), 400 + 240);
+        let registry = get_registry(&program, &registry_address(&fixture, &alice.pubkey()));
+        assert_eq!(registry.shares, 0);
+        assert_eq!(registry.token_balance, 0);
+
+        let res = send(&mut program, &[emergency_withdraw_ix(&bob.pubkey())], &bob, &[&bob]);
+        assert!(res.is_ok(), "Emergency withdraw should succeed: {:?}", res.err());
+        assert_eq!(token_balance(&program, &bob_ata), 600 + 120);
+
+        // The queued request takes the same haircut
+        let emergency_claim_ix = |withdrawer: &Pubkey| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::EmergencyClaimWithdraw {
+                withdrawer: *withdrawer,
+                vault: fixture.vault,
+                withdrawal_request: withdrawal_request(0),
+                mint: fixture.mint.pubkey(),
+                withdrawer_token_account: bob_ata,
+                vault_token_reserve: fixture.reserve,
+                extra_account_meta_list: Some(fixture.extra_account_meta_list),
+                transfer_hook_program: Some(transfer_hook::ID),
+                vault_whitelist: Some(fixture.vault_whitelist),
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::EmergencyClaimWithdraw { request_id: 0, max_fee: 0 }.data(),
+        };
+        let res = send(&mut program, &[emergency_claim_ix(&alice.pubkey())], &alice, &[&alice]);
+        assert!(res.unwrap_err().contains("WithdrawalRequestNotFound"));
+        let res = send(&mut program, &[emergency_claim_ix(&bob.pubkey())], &bob, &[&bob]);
+        assert!(res.is_ok(), "Emergency claim should succeed: {:?}", res.err());
+        assert_eq!(token_balance(&program, &bob_ata), 600 + 120 + 40);
+        assert!(program.get_account(&withdrawal_request(0)).is_none_or(|a| a.data.is_empty()));
+
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.num_depositors, 0);
+        assert_eq!(vault.token_reserve_amount, 0);
+        assert_eq!(vault.queued_withdrawals, 0);
+        assert_eq!(token_balance(&program, &fixture.reserve), 0);
+    }
+
+    #[test]
+    fn test_emergency_shutdown_haircuts_fees() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+        let mint = fixture.mint.pubkey();
+
+        let depositor = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let depositor_ata = fund_user(&mut program, &payer, &fixture, &depositor.pubkey(), 1_000);
+        let fee_recipient = create_ata(&mut program, &payer, &payer.pubkey(), &mint);
+
+        let admin_ix = |data: Vec<u8>| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::VaultAdmin {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+            }
+            .to_account_metas(None),
+            data,
+        };
+        let set_fees = crate::instruction::SetFees {
+            deposit_fee_bps: crate::utils::MAX_FEE_BPS,
+            withdraw_fee_bps: 0,
+            performance_fee_bps: 0,
+        };
+        let res = send(
+            &mut program,
+            &[admin_ix(set_fees.data()), admin_ix(crate::instruction::SetFeeRecipient { fee_recipient }.data())],
+            &payer,
+            &[&payer],
+        );
+        assert!(res.is_ok(), "Setting fees should succeed: {:?}", res.err());
+        let mut clock = program.get_sysvar::<Clock>();
+        clock.unix_timestamp = get_vault(&program, &fixture.vault).pending_fees_at;
+        program.set_sysvar::<Clock>(&clock);
+        let res = send(&mut program, &[admin_ix(crate::instruction::ApplyFees {}.data())], &payer, &[&payer]);
+        assert!(res.is_ok(), "Applying fees should succeed: {:?}", res.err());
+
+        let res = send(&mut program, &[deposit_ix(&fixture, &depositor.pubkey(), 1_000)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+        assert_eq!(get_vault(&program, &fixture.vault).accrued_fees, 100);
+
+        let res = send(&mut program, &[admin_ix(crate::instruction::EmergencyShutdown {}.data())], &payer, &[&payer]);
+        assert!(res.is_ok(), "Shutting the vault down should succeed: {:?}", res.err());
+
+        // Half of the 1_000 tokens the vault owes go missing from the reserve
+        let mut reserve_account = program.get_account(&fixture.reserve).unwrap();
+        {
+            let mut reserve_state =
+                StateWithExtensionsMut::<spl_token_2022::state::Account>::unpack(&mut reserve_account.data).unwrap();
+            reserve_state.base.amount = 500;
+            reserve_state.pack_base();
+        }
+        program.set_account(fixture.reserve, reserve_account).unwrap();
+
+        // The fee recipient takes the same haircut as the depositor
+        let collect_fees_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::CollectFees {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+                mint,
+                fee_recipient,
+                vault_token_reserve: fixture.reserve,
+                extra_account_meta_list: Some(fixture.extra_account_meta_list),
+                transfer_hook_program: Some(transfer_hook::ID),
+                vault_whitelist: Some(fixture.vault_whitelist),
+                token_program: TOKEN_PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::CollectFees {}.data(),
+        };
+        let res = send(&mut program, &[collect_fees_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Collecting fees should succeed: {:?}", res.err());
+        assert_eq!(token_balance(&program, &fee_recipient), 50);
+        assert_eq!(get_vault(&program, &fixture.vault).accrued_fees, 0);
+
+        let emergency_withdraw_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::EmergencyWithdraw {
+                withdrawer: depositor.pubkey(),
+                vault: fixture.vault,
+                vault_registry_entry: registry_address(&fixture, &depositor.pubkey()),
+                mint,
+                withdrawer_token_account: depositor_ata,
+                vault_token_reserve: fixture.reserve,
+                share_mint: fixture.share_mint,
+                withdrawer_share_account: share_account_address(&fixture, &depositor.pubkey()),
+                extra_account_meta_list: Some(fixture.extra_account_meta_list),
+                transfer_hook_program: Some(transfer_hook::ID),
+                vault_whitelist: Some(fixture.vault_whitelist),
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                share_token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+                event_authority: event_authority(),
+                program: PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::EmergencyWithdraw { max_fee: 0 }.data(),
+        };
+        let res = send(&mut program, &[emergency_withdraw_ix], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Emergency withdraw should succeed: {:?}", res.err());
+        assert_eq!(token_balance(&program, &depositor_ata), 450);
+        assert_eq!(token_balance(&program, &fixture.reserve), 0);
+    }
+
+    #[test]
+    fn test_deposit_for() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+        let mint = fixture.mint.pubkey();
+
+        let parent = Keypair::new();
+        let child = Keypair::new();
+        program.airdrop(&parent.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        program.airdrop(&child.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let parent_ata = fund_user(&mut program, &payer, &fixture, &parent.pubkey(), 1_000);
+
+        let whitelist = |user: &Pubkey| {
+            Pubkey::find_program_address(&[b"whitelist", mint.as_ref(), user.as_ref()], &transfer_hook::ID).0
+        };
+        let deposit_for_ix = |amount: u64| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::DepositFor {
+                payer: parent.pubkey(),
+                beneficiary: child.pubkey(),
+                vault: fixture.vault,
+                vault_registry_entry: registry_address(&fixture, &child.pubkey()),
+                mint,
+                payer_token_account: parent_ata,
+                vault_token_reserve: fixture.reserve,
+                share_mint: fixture.share_mint,
+                beneficiary_share_account: share_account_address(&fixture, &child.pubkey()),
+                extra_account_meta_list: Some(fixture.extra_account_meta_list),
+                transfer_hook_program: Some(transfer_hook::ID),
+                payer_whitelist: Some(whitelist(&parent.pubkey())),
+                beneficiary_whitelist: Some(whitelist(&child.pubkey())),
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                share_token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+                event_authority: event_authority(),
+                program: PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::DepositFor { amount, max_fee: 0 }.data(),
+        };
+
+        let res = send(&mut program, &[deposit_for_ix(500)], &parent, &[&parent]);
+        assert!(res.unwrap_err().contains("BeneficiaryNotWhitelisted"));
+
+        add_to_whitelist(&mut program, &payer, &mint, &child.pubkey());
+        program.expire_blockhash();
+        let res = send(&mut program, &[deposit_for_ix(500)], &parent, &[&parent]);
+        assert!(res.is_ok(), "Deposit for a whitelisted beneficiary should succeed: {:?}", res.err());
+
+        assert_eq!(token_balance(&program, &parent_ata), 500);
+        assert_eq!(token_balance(&program, &share_account_address(&fixture, &child.pubkey())), 500);
+        let registry = get_registry(&program, &registry_address(&fixture, &child.pubkey()));
+        assert_eq!(registry.user, child.pubkey());
+        assert_eq!(registry.shares, 500);
+        assert_eq!(registry.token_balance, 500);
+        assert_eq!(registry.locked_until, 0, "The payer cannot lock the beneficiary's position");
+        assert!(program.get_account(&registry_address(&fixture, &parent.pubkey())).is_none());
+
+        // The position belongs to the beneficiary
+        let child_ata = create_ata(&mut program, &payer, &child.pubkey(), &mint);
+        let withdraw_ix = |user: &Pubkey| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: withdraw_accounts(&fixture, user),
+            data: crate::instruction::Withdraw { amount: 200, max_fee: 0 }.data(),
+        };
+        let res = send(&mut program, &[withdraw_ix(&parent.pubkey())], &parent, &[&parent]);
+        assert!(res.is_err(), "The payer has no position to withdraw from");
+        let res = send(&mut program, &[withdraw_ix(&child.pubkey())], &child, &[&child]);
+        assert!(res.is_ok(), "The beneficiary should withdraw: {:?}", res.err());
+        assert_eq!(token_balance(&program, &child_ata), 200);
+    }
+
+    #[test]
+    fn test_withdraw_to_recipient() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+        let mint = fixture.mint.pubkey();
+
+        let depositor = Keypair::new();
+        let cold_wallet = Keypair::new();
+        let stranger = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        fund_user(&mut program, &payer, &fixture, &depositor.pubkey(), 1_000);
+        let res = send(&mut program, &[deposit_ix(&fixture, &depositor.pubkey(), 600)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+
+        add_to_whitelist(&mut program, &payer, &mint, &cold_wallet.pubkey());
+        let cold_wallet_ata = create_ata(&mut program, &payer, &cold_wallet.pubkey(), &mint);
+        let stranger_ata = create_ata(&mut program, &payer, &stranger.pubkey(), &mint);
+
+        let withdraw_to_ix = |recipient: &Pubkey, recipient_token_account: Pubkey| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Withdraw {
+                withdrawer: depositor.pubkey(),
+                vault: fixture.vault,
+                vault_registry_entry: registry_address(&fixture, &depositor.pubkey()),
+                mint,
+                recipient_token_account,
+                recipient_whitelist: Some(Pubkey::find_program_address(
+                    &[b"whitelist", mint.as_ref(), recipient.as_ref()],
+                    &transfer_hook::ID,
+                )
+                .0),
+                vault_token_reserve: fixture.reserve,
+                share_mint: fixture.share_mint,
+                withdrawer_share_account: share_account_address(&fixture, &depositor.pubkey()),
+                extra_account_meta_list: Some(fixture.extra_account_meta_list),
+                transfer_hook_program: Some(transfer_hook::ID),
+                vault_whitelist: Some(fixture.vault_whitelist),
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                share_token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+                event_authority: event_authority(),
+                program: PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::Withdraw { amount: 250, max_fee: 0 }.data(),
+        };
+
+        let res = send(&mut program, &[withdraw_to_ix(&stranger.pubkey(), stranger_ata)], &depositor, &[&depositor]);
+        assert!(res.unwrap_err().contains("RecipientNotWhitelisted"));
+
+        let res = send(&mut program, &[withdraw_to_ix(&cold_wallet.pubkey(), cold_wallet_ata)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Withdrawing to a whitelisted recipient should succeed: {:?}", res.err());
+        assert_eq!(token_balance(&program, &cold_wallet_ata), 250);
+        assert_eq!(get_registry(&program, &registry_address(&fixture, &depositor.pubkey())).shares, 350);
+    }
+
+    #[test]
+    fn test_batch_deposit_and_payout() {
+        use anchor_lang::prelude::AccountMeta;
+
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+        let mint = fixture.mint.pubkey();
+
+        let employer = Keypair::new();
+        let alice = Keypair::new();
+        let bob = Keypair::new();
+        let carol = Keypair::new();
+        for user in [&employer, &bob] {
+            program.airdrop(&user.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        }
+        let employer_ata = fund_user(&mut program, &payer, &fixture, &employer.pubkey(), 1_000);
+        let bob_ata = fund_user(&mut program, &payer, &fixture, &bob.pubkey(), 100);
+        add_to_whitelist(&mut program, &payer, &mint, &alice.pubkey());
+        let alice_ata = create_ata(&mut program, &payer, &alice.pubkey(), &mint);
+
+        // Bob already has a position, Alice gets one created by the batch
+        let res = send(&mut program, &[deposit_ix(&fixture, &bob.pubkey(), 100)], &bob, &[&bob]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+
+        let whitelist = |user: &Pubkey| {
+            Pubkey::find_program_address(&[b"whitelist", mint.as_ref(), user.as_ref()], &transfer_hook::ID).0
+        };
+        let batch_deposit_ix = |beneficiaries: &[Pubkey], amounts: Vec<u64>| {
+            let mut accounts = crate::accounts::BatchDeposit {
+                payer: employer.pubkey(),
+                vault: fixture.vault,
+                mint,
+                payer_token_account: employer_ata,
+                vault_token_reserve: fixture.reserve,
+                share_mint: fixture.share_mint,
+                extra_account_meta_list: Some(fixture.extra_account_meta_list),
+                transfer_hook_program: Some(transfer_hook::ID),
+                payer_whitelist: Some(whitelist(&employer.pubkey())),
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                share_token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+                event_authority: event_authority(),
+                program: PROGRAM_ID,
+            }
+            .to_account_metas(None);
+            for beneficiary in beneficiaries {
+                accounts.push(AccountMeta::new_readonly(*beneficiary, false));
+                accounts.push(AccountMeta::new(registry_address(&fixture, beneficiary), false));
+                accounts.push(AccountMeta::new(share_account_address(&fixture, beneficiary), false));
+                accounts.push(AccountMeta::new_readonly(whitelist(beneficiary), false));
+            }
+            Instruction {
+                program_id: PROGRAM_ID,
+                accounts,
+                data: crate::instruction::BatchDeposit { amounts }.data(),
+            }
+        };
+
+        let res = send(
+            &mut program,
+            &[batch_deposit_ix(&[alice.pubkey(), carol.pubkey()], vec![300, 200])],
+            &employer,
+            &[&employer],
+        );
+        assert!(res.unwrap_err().contains("BeneficiaryNotWhitelisted"));
+
+        let res = send(&mut program, &[batch_deposit_ix(&[alice.pubkey()], vec![300, 200])], &employer, &[&employer]);
+        assert!(res.unwrap_err().contains("InvalidBatch"));
+
+        let mut readonly_registry_ix = batch_deposit_ix(&[alice.pubkey()], vec![300]);
+        let registry_index = readonly_registry_ix.accounts.len() - 3;
+        readonly_registry_ix.accounts[registry_index].is_writable = false;
+        let res = send(&mut program, &[readonly_registry_ix], &employer, &[&employer]);
+        assert!(res.unwrap_err().contains("InvalidBatch"));
+
+        // Lamports sent to Alice's registry address beforehand do not block its creation
+        program.airdrop(&registry_address(&fixture, &alice.pubkey()), 1_000).unwrap();
+
+        let res = send(
+            &mut program,
+            &[batch_deposit_ix(&[alice.pubkey(), bob.pubkey()], vec![300, 200])],
+            &employer,
+            &[&employer],
+        );
+        assert!(res.is_ok(), "Batch deposit should succeed: {:?}", res.err());
+
+        assert_eq!(token_balance(&program, &employer_ata), 500);
+        let alice_registry = get_registry(&program, &registry_address(&fixture, &alice.pubkey()));
+        assert_eq!(alice_registry.user, alice.pubkey());
+        assert_eq!(alice_registry.shares, 300);
+        assert_eq!(alice_registry.token_balance, 300);
+        let bob_registry = get_registry(&program, &registry_address(&fixture, &bob.pubkey()));
+        assert_eq!(bob_registry.shares, 300);
+        assert_eq!(bob_registry.num_deposits, 2);
+        assert_eq!(token_balance(&program, &share_account_address(&fixture, &alice.pubkey())), 300);
+
+        let vault = get_vault(&program, &fixture.vault);
+        assert_eq!(vault.token_reserve_amount, 600);
+        assert_eq!(vault.num_depositors, 2);
+        assert_eq!(vault.num_deposits, 3);
+        assert_eq!(vault.open_positions, 2);
+
+        let batch_payout_ix = |authority: &Pubkey| {
+            let mut accounts = crate::accounts::BatchPayout {
+                vault_authority: *authority,
+                vault: fixture.vault,
+                mint,
+                vault_token_reserve: fixture.reserve,
+                extra_account_meta_list: Some(fixture.extra_account_meta_list),
+                transfer_hook_program: Some(transfer_hook::ID),
+                vault_whitelist: Some(fixture.vault_whitelist),
+                token_program: TOKEN_PROGRAM_ID,
+            }
+            .to_account_metas(None);
+            for (recipient, recipient_ata) in [(alice.pubkey(), alice_ata), (bob.pubkey(), bob_ata)] {
+                accounts.push(AccountMeta::new(recipient_ata, false));
+                accounts.push(AccountMeta::new_readonly(whitelist(&recipient), false));
+            }
+            Instruction {
+                program_id: PROGRAM_ID,
+                accounts,
+                data: crate::instruction::BatchPayout { amounts: vec![50, 70], max_fee: 0 }.data(),
+            }
+        };
+
+        let res = send(&mut program, &[batch_payout_ix(&bob.pubkey())], &bob, &[&bob]);
+        assert!(res.unwrap_err().contains("Unauthorized"));
+
+        let res = send(&mut program, &[batch_payout_ix(&payer.pubkey())], &payer, &[&payer]);
+        assert!(res.is_ok(), "Batch payout should succeed: {:?}", res.err());
+        assert_eq!(token_balance(&program, &alice_ata), 50);
+        assert_eq!(token_balance(&program, &bob_ata), 70);
+        assert_eq!(get_vault(&program, &fixture.vault).token_reserve_amount, 480);
+    }
+
+    #[test]
+    fn test_vault_without_transfer_hook() {
+        let (mut program, payer) = setup();
+        let fixture = setup_hookless_vault(&mut program, &payer, None);
+        let mint = fixture.mint.pubkey();
+
+        let depositor = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let depositor_ata = create_ata(&mut program, &payer, &depositor.pubkey(), &mint);
+        mint_tokens_to(&mut program, &mint, &depositor_ata, &payer, 1_000);
+
+        let res = send(&mut program, &[hookless_deposit_ix(&fixture, &depositor.pubkey(), 600, 0)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit without hook accounts should succeed: {:?}", res.err());
+        assert_eq!(token_balance(&program, &fixture.reserve), 600);
+
+        let res = send(&mut program, &[hookless_withdraw_ix(&fixture, &depositor.pubkey(), 250, 0)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Withdraw without hook accounts should succeed: {:?}", res.err());
+        assert_eq!(token_balance(&program, &depositor_ata), 650);
+        assert_eq!(get_registry(&program, &registry_address(&fixture, &depositor.pubkey())).shares, 350);
+    }
+
+    #[test]
+    fn test_legacy_token_vault() {
+        use anchor_lang::solana_program::system_instruction::create_account;
+        use anchor_spl::associated_token::spl_associated_token_account::instruction::create_associated_token_account;
+
+        let (mut program, payer) = setup();
+        let legacy_token_program = spl_token::ID;
+
+        let mint = Keypair::new();
+        let space = spl_token::state::Mint::LEN;
+        let create_mint_ixs = [
+            create_account(
+                &payer.pubkey(),
+                &mint.pubkey(),
+                program.minimum_balance_for_rent_exemption(space),
+                space as u64,
+                &legacy_token_program,
+            ),
+            spl_token::instruction::initialize_mint2(&legacy_token_program, &mint.pubkey(), &payer.pubkey(), None, 9)
+                .unwrap(),
+        ];
+        let res = send(&mut program, &create_mint_ixs, &payer, &[&payer, &mint]);
+        assert!(res.is_ok(), "Creating the SPL Token mint should succeed: {:?}", res.err());
+
+        let mint_key = mint.pubkey();
+        let token_account = |owner: &Pubkey| {
+            associated_token::get_associated_token_address_with_program_id(owner, &mint_key, &legacy_token_program)
+        };
+        let (vault, _) = Pubkey::find_program_address(
+            &[b"vault", payer.pubkey().as_ref(), mint.pubkey().as_ref(), &0u64.to_le_bytes()],
+            &PROGRAM_ID,
+        );
+        let (share_mint, _) = Pubkey::find_program_address(&[b"share_mint", vault.as_ref()], &PROGRAM_ID);
+        let reserve = token_account(&vault);
+        let fixture = VaultFixture {
+            mint,
+            vault,
+            reserve,
+            share_mint,
+            extra_account_meta_list: Pubkey::default(),
+            vault_whitelist: Pubkey::default(),
+        };
+        let mint = fixture.mint.pubkey();
+
+        let init_vault_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::InitializeVault {
+                vault_authority: payer.pubkey(),
+                mint,
+                vault,
+                share_mint,
+                token_reserve: reserve,
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: legacy_token_program,
+                share_token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+                event_authority: event_authority(),
+                program: PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::InitializeVault { vault_index: 0 }.data(),
+        };
+        let res = send(&mut program, &[init_vault_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Initializing a vault for an SPL Token mint should succeed: {:?}", res.err());
+        assert_eq!(program.get_account(&reserve).unwrap().owner, legacy_token_program);
+
+        let depositor = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let depositor_ata = token_account(&depositor.pubkey());
+        let fund_ixs = [
+            create_associated_token_account(&payer.pubkey(), &depositor.pubkey(), &mint, &legacy_token_program),
+            spl_token::instruction::mint_to(&legacy_token_program, &mint, &depositor_ata, &payer.pubkey(), &[], 1_000)
+                .unwrap(),
+        ];
+        let res = send(&mut program, &fund_ixs, &payer, &[&payer]);
+        assert!(res.is_ok(), "Funding the depositor should succeed: {:?}", res.err());
+
+        let deposit_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Deposit {
+                depositor: depositor.pubkey(),
+                vault,
+                vault_registry_entry: registry_address(&fixture, &depositor.pubkey()),
+                mint,
+                depositor_token_account: depositor_ata,
+                vault_token_reserve: reserve,
+                share_mint,
+                depositor_share_account: share_account_address(&fixture, &depositor.pubkey()),
+                transfer_hook_program: None,
+                extra_account_meta_list: None,
+                depositor_whitelist_PDA: None,
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: legacy_token_program,
+                share_token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+                event_authority: event_authority(),
+                program: PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::Deposit { amount: 600, lock_duration: 0, max_fee: 0 }.data(),
+        };
+        let res = send(&mut program, &[deposit_ix], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit of an SPL Token mint should succeed: {:?}", res.err());
+        assert_eq!(token_balance(&program, &reserve), 600);
+        assert_eq!(get_vault(&program, &vault).token_reserve_amount, 600);
+
+        let withdraw_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Withdraw {
+                withdrawer: depositor.pubkey(),
+                vault,
+                vault_registry_entry: registry_address(&fixture, &depositor.pubkey()),
+                mint,
+                recipient_token_account: depositor_ata,
+                recipient_whitelist: None,
+                vault_token_reserve: reserve,
+                share_mint,
+                withdrawer_share_account: share_account_address(&fixture, &depositor.pubkey()),
+                extra_account_meta_list: None,
+                transfer_hook_program: None,
+                vault_whitelist: None,
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: legacy_token_program,
+                share_token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+                event_authority: event_authority(),
+                program: PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::Withdraw { amount: 250, max_fee: 0 }.data(),
+        };
+        let res = send(&mut program, &[withdraw_ix], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Withdraw of an SPL Token mint should succeed: {:?}", res.err());
+        assert_eq!(token_balance(&program, &depositor_ata), 650);
+        assert_eq!(token_balance(&program, &reserve), 350);
+        assert_eq!(get_registry(&program, &registry_address(&fixture, &depositor.pubkey())).shares, 350);
+    }
+
+    #[test]
+    fn test_hooked_mint_requires_hook_accounts() {
+        let (mut program, payer) = setup();
+        let fixture = setup_vault(&mut program, &payer, 500);
+
+        let depositor = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        fund_user(&mut program, &payer, &fixture, &depositor.pubkey(), 1_000);
+
+        let mut deposit_ix = deposit_ix(&fixture, &depositor.pubkey(), 600);
+        // Anchor passes the program id for a missing optional account
+        for meta in deposit_ix.accounts.iter_mut().filter(|meta| meta.pubkey == transfer_hook::ID) {
+            *meta = anchor_lang::prelude::AccountMeta::new_readonly(PROGRAM_ID, false);
+        }
+        let res = send(&mut program, &[deposit_ix], &depositor, &[&depositor]);
+        assert!(res.unwrap_err().contains("MissingTransferHookAccounts"));
+    }
+
+    #[test]
+    fn test_transfer_fee_mint() {
+        let (mut program, payer) = setup();
+        // 1% transfer fee
+        let fixture = setup_hookless_vault(&mut program, &payer, Some(100));
+        let mint = fixture.mint.pubkey();
+
+        let depositor = Keypair::new();
+        program.airdrop(&depositor.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let depositor_ata = create_ata(&mut program, &payer, &depositor.pubkey(), &mint);
+        mint_tokens_to(&mut program, &mint, &depositor_ata, &payer, 1_000);
+        let registry = registry_address(&fixture, &depositor.pubkey());
+
+        let res = send(&mut program, &[hookless_deposit_ix(&fixture, &depositor.pubkey(), 1_000, 5)], &depositor, &[&depositor]);
+        assert!(res.unwrap_err().contains("TransferFeeExceedsMax"));
+
+        // Only the 990 tokens the reserve received are credited
+        let res = send(&mut program, &[hookless_deposit_ix(&fixture, &depositor.pubkey(), 1_000, 10)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Deposit should succeed: {:?}", res.err());
+        assert_eq!(token_balance(&program, &fixture.reserve), 990);
+        assert_eq!(get_vault(&program, &fixture.vault).token_reserve_amount, 990);
+        assert_eq!(get_registry(&program, &registry).token_balance, 990);
+        assert_eq!(get_registry(&program, &registry).shares, 990);
+
+        let res = send(&mut program, &[hookless_withdraw_ix(&fixture, &depositor.pubkey(), 500, 4)], &depositor, &[&depositor]);
+        assert!(res.unwrap_err().contains("TransferFeeExceedsMax"));
+
+        // The reserve pays out 500, the depositor receives them net of the fee
+        let res = send(&mut program, &[hookless_withdraw_ix(&fixture, &depositor.pubkey(), 500, 5)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Withdraw should succeed: {:?}", res.err());
+        assert_eq!(token_balance(&program, &depositor_ata), 495);
+        assert_eq!(token_balance(&program, &fixture.reserve), 490);
+        assert_eq!(get_vault(&program, &fixture.vault).token_reserve_amount, 490);
+
+        // Emergency exits bound the transfer fee the same way
+        let shutdown_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::VaultAdmin {
+                vault_authority: payer.pubkey(),
+                vault: fixture.vault,
+                vault_roles: None,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::EmergencyShutdown {}.data(),
+        };
+        let res = send(&mut program, &[shutdown_ix], &payer, &[&payer]);
+        assert!(res.is_ok(), "Shutting the vault down should succeed: {:?}", res.err());
+
+        let emergency_withdraw_ix = |max_fee: u64| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::EmergencyWithdraw {
+                withdrawer: depositor.pubkey(),
+                vault: fixture.vault,
+                vault_registry_entry: registry,
+                mint,
+                withdrawer_token_account: depositor_ata,
+                vault_token_reserve: fixture.reserve,
+                share_mint: fixture.share_mint,
+                withdrawer_share_account: share_account_address(&fixture, &depositor.pubkey()),
+                extra_account_meta_list: None,
+                transfer_hook_program: None,
+                vault_whitelist: None,
+                associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+                token_program: TOKEN_PROGRAM_ID,
+                share_token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+                event_authority: event_authority(),
+                program: PROGRAM_ID,
+            }
+            .to_account_metas(None),
+            data: crate::instruction::EmergencyWithdraw { max_fee }.data(),
+        };
+        let res = send(&mut program, &[emergency_withdraw_ix(4)], &depositor, &[&depositor]);
+        assert!(res.unwrap_err().contains("TransferFeeExceedsMax"));
+        let res = send(&mut program, &[emergency_withdraw_ix(5)], &depositor, &[&depositor]);
+        assert!(res.is_ok(), "Emergency withdraw should succeed: {:?}", res.err());
+        assert_eq!(token_balance(&program, &depositor_ata), 495 + 485);
+        assert_eq!(token_balance(&program, &fixture.reserve), 0);
+    }
+
+    #[test]
+    fn test_fee_amount() {
+        use crate::utils::fee_amount;
+
+        assert_eq!(fee_amount(1_000, 0).unwrap(), 0);
+        assert_eq!(fee_amount(1_000, 100).unwrap(), 10);
+        assert_eq!(fee_amount(1_000, 10_000).unwrap(), 1_000);
+
+        // Rounded up in favour of the vault
+        assert_eq!(fee_amount(990, 200).unwrap(), 20);
+        assert_eq!(fee_amount(1, 1).unwrap(), 1);
+        assert_eq!(fee_amount(u64::MAX, 10_000).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn test_share_conversion() {
+        use crate::utils::{convert_to_assets, convert_to_shares, Rounding};
+
+        // Empty vault mints 1:1
+        assert_eq!(convert_to_shares(500, 0, 0, Rounding::Down).unwrap(), 500);
+
+        // Reserve doubled by yield: new deposits get half as many shares
+        assert_eq!(convert_to_shares(100, 1_000, 500, Rounding::Down).unwrap(), 50);
+        assert_eq!(convert_to_assets(50, 1_000, 500, Rounding::Down).unwrap(), 99);
+
+        // Withdrawals round against the withdrawer
+        assert_eq!(convert_to_shares(1, 1_000, 500, Rounding::Down).unwrap(), 0);
+        assert_eq!(convert_to_shares(1, 1_000, 500, Rounding::Up).unwrap(), 1);
+
+        // A donation into an empty vault is owned by the virtual share, so the
+        // next deposit still prices against it (and is rejected if it mints nothing)
+        assert_eq!(convert_to_shares(2_002, 1_000, 0, Rounding::Down).unwrap(), 2);
+        assert_eq!(convert_to_shares(1_000, 1_000, 0, Rounding::Down).unwrap(), 0);
+    }
 }
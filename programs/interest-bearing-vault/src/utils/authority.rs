@@ -0,0 +1,62 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::VaultError;
+use crate::state::{Multisig, Role, Vault, VaultRoles};
+
+/// Checks that `authority` approved the instruction.
+///
+/// A single-key authority approves by being `signer`. A multisig authority is passed as
+/// the first remaining account, followed by its co-signers, and approves once `threshold`
+/// distinct members signed (`signer` counts if it is a member). An account owned by this
+/// program never approves by signing alone, so a multisig always goes through its threshold.
+pub fn check_authority(
+    authority: Pubkey,
+    signer: &AccountInfo,
+    remaining_accounts: &[AccountInfo],
+) -> Result<()> {
+    if signer.is_signer && signer.key() == authority && *signer.owner != crate::ID {
+        return Ok(());
+    }
+
+    let (multisig_info, co_signers) = remaining_accounts
+        .split_first()
+        .ok_or(VaultError::Unauthorized)?;
+    require_keys_eq!(multisig_info.key(), authority, VaultError::Unauthorized);
+    require_keys_eq!(*multisig_info.owner, crate::ID, VaultError::Unauthorized);
+
+    let data = multisig_info.try_borrow_data()?;
+    let multisig = Multisig::try_deserialize(&mut &data[..])?;
+
+    let signed: Vec<Pubkey> = std::iter::once(signer.key())
+        .filter(|_| signer.is_signer)
+        .chain(co_signers.iter().filter(|a| a.is_signer).map(|a| a.key()))
+        .collect();
+    require!(
+        multisig.approvals(&signed) >= multisig.threshold as usize,
+        VaultError::MultisigThresholdNotMet
+    );
+
+    Ok(())
+}
+
+/// Checks that the signer holds `role` in `vault_roles`, or that the vault authority
+/// (which holds every role) approved the instruction as in `check_authority`.
+pub fn check_role(
+    vault: &Vault,
+    vault_roles: Option<&VaultRoles>,
+    role: Role,
+    signer: &AccountInfo,
+    remaining_accounts: &[AccountInfo],
+) -> Result<()> {
+    if signer.is_signer && vault_roles.is_some_and(|roles| roles.has_role(signer.key, role)) {
+        return Ok(());
+    }
+
+    check_authority(vault.vault_authority, signer, remaining_accounts).map_err(|e| {
+        if e == VaultError::Unauthorized.into() {
+            role.unauthorized_error()
+        } else {
+            e
+        }
+    })
+}
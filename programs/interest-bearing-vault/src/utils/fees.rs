@@ -0,0 +1,19 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::VaultError;
+
+/// Fees are expressed in basis points of the amount they apply to.
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
+/// Highest fee `set_fees` accepts, 10%
+pub const MAX_FEE_BPS: u16 = 1_000;
+
+/// Fee owed on `amount` at `fee_bps`, rounded up in favour of the vault.
+pub fn fee_amount(amount: u64, fee_bps: u16) -> Result<u64> {
+    let numerator = (amount as u128)
+        .checked_mul(fee_bps as u128)
+        .ok_or(VaultError::Overflow)?;
+
+    let fee = numerator.div_ceil(BPS_DENOMINATOR as u128);
+    u64::try_from(fee).map_err(|_| VaultError::Overflow.into())
+}
@@ -0,0 +1,83 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022::{
+    extension::{
+        interest_bearing_mint::InterestBearingConfig,
+        BaseStateWithExtensions, StateWithExtensions,
+    },
+    state::Mint as Token2022Mint,
+};
+
+use crate::errors::VaultError;
+use crate::state::Vault;
+
+/// Interest-inclusive value of `amount` raw tokens at `unix_timestamp`, in raw units.
+/// Mints without the InterestBearingConfig extension earn nothing and return `amount`.
+pub fn interest_inclusive_amount(mint: &AccountInfo, amount: u64, unix_timestamp: i64) -> Result<u64> {
+    let data = mint.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<Token2022Mint>::unpack(&data)?;
+
+    match mint_state.get_extension::<InterestBearingConfig>() {
+        Ok(config) => apply_interest(config, amount, unix_timestamp),
+        Err(_) => Ok(amount),
+    }
+}
+
+/// Copy of the mint's InterestBearingConfig.
+pub fn interest_bearing_config(mint: &AccountInfo) -> Result<InterestBearingConfig> {
+    let data = mint.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<Token2022Mint>::unpack(&data)?;
+
+    mint_state
+        .get_extension::<InterestBearingConfig>()
+        .copied()
+        .map_err(|_| error!(VaultError::NotInterestBearing))
+}
+
+/// Same computation as Token-2022's `amount_to_ui_amount`, without the decimal scaling,
+/// so the result matches what wallets display for `amount`.
+pub fn apply_interest(config: &InterestBearingConfig, amount: u64, unix_timestamp: i64) -> Result<u64> {
+    let scaled = config
+        .amount_to_ui_amount(amount, 0, unix_timestamp)
+        .and_then(|ui_amount| ui_amount.parse::<f64>().ok())
+        .ok_or(VaultError::InterestCalculation)?;
+
+    if !scaled.is_finite() || scaled < 0.0 || scaled >= u64::MAX as f64 {
+        return err!(VaultError::InterestCalculation);
+    }
+
+    Ok(scaled.floor() as u64)
+}
+
+/// Raw tokens `amount` earned between `from` and `to`: what grows `amount` by the same
+/// factor its interest-inclusive value grew over that period.
+pub fn interest_earned(mint: &AccountInfo, amount: u64, from: i64, to: i64) -> Result<u64> {
+    let balance_then = interest_inclusive_amount(mint, amount, from)?;
+    let balance_now = interest_inclusive_amount(mint, amount, to)?;
+    if balance_then == 0 || balance_now <= balance_then {
+        return Ok(0);
+    }
+
+    let target = (amount as u128)
+        .checked_mul(balance_now as u128)
+        .ok_or(VaultError::Overflow)?
+        / balance_then as u128;
+
+    Ok(u64::try_from(target)
+        .map_err(|_| VaultError::Overflow)?
+        .saturating_sub(amount))
+}
+
+/// Books the interest `token_reserve_amount` earned since the last checkpoint as
+/// `unrealized_interest`. Must run before the reserve changes, so tokens only earn
+/// interest from the moment they joined it.
+pub fn checkpoint_interest(vault: &mut Vault, mint: &AccountInfo) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let earned = interest_earned(mint, vault.token_reserve_amount, vault.last_interest_realization, now)?;
+
+    vault.unrealized_interest = vault.unrealized_interest
+        .checked_add(earned)
+        .ok_or(VaultError::Overflow)?;
+    vault.last_interest_realization = now;
+
+    Ok(())
+}
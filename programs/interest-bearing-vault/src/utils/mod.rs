@@ -0,0 +1,15 @@
+mod account;
+mod authority;
+mod fees;
+mod interest;
+mod shares;
+mod transfer;
+mod whitelist;
+
+pub use account::*;
+pub use authority::*;
+pub use fees::*;
+pub use interest::*;
+pub use shares::*;
+pub use transfer::*;
+pub use whitelist::*;
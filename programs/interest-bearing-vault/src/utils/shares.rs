@@ -0,0 +1,48 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::VaultError;
+
+/// Virtual shares and assets added to both sides of the exchange rate.
+/// An empty vault starts at 1:1 and a first depositor cannot inflate the share price
+/// by donating to the reserve.
+const VIRTUAL_OFFSET: u128 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    Down,
+    Up,
+}
+
+fn mul_div(value: u64, numerator: u64, denominator: u64, rounding: Rounding) -> Result<u64> {
+    let numerator = (value as u128)
+        .checked_mul(numerator as u128 + VIRTUAL_OFFSET)
+        .ok_or(VaultError::Overflow)?;
+    let denominator = denominator as u128 + VIRTUAL_OFFSET;
+
+    let mut result = numerator / denominator;
+    if rounding == Rounding::Up && numerator % denominator != 0 {
+        result += 1;
+    }
+
+    u64::try_from(result).map_err(|_| VaultError::Overflow.into())
+}
+
+/// Number of shares `assets` are worth at the current exchange rate.
+pub fn convert_to_shares(
+    assets: u64,
+    total_assets: u64,
+    total_shares: u64,
+    rounding: Rounding,
+) -> Result<u64> {
+    mul_div(assets, total_shares, total_assets, rounding)
+}
+
+/// Amount of assets `shares` can be redeemed for at the current exchange rate.
+pub fn convert_to_assets(
+    shares: u64,
+    total_assets: u64,
+    total_shares: u64,
+    rounding: Rounding,
+) -> Result<u64> {
+    mul_div(shares, total_assets, total_shares, rounding)
+}
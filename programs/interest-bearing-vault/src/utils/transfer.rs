@@ -0,0 +1,192 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use anchor_spl::token_2022::spl_token_2022::{
+    extension::{
+        transfer_fee::{TransferFeeAmount, TransferFeeConfig},
+        transfer_hook, BaseStateWithExtensions, StateWithExtensions,
+    },
+    state::{Account as Token2022Account, Mint as Token2022Mint},
+};
+use spl_token_2022::onchain;
+
+use crate::errors::VaultError;
+use crate::state::Vault;
+
+/// Program id of the mint's transfer hook, `None` for SPL Token mints and Token-2022
+/// mints without the TransferHook extension.
+pub fn transfer_hook_program_id(mint: &AccountInfo) -> Result<Option<Pubkey>> {
+    let data = mint.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<Token2022Mint>::unpack(&data)?;
+
+    Ok(transfer_hook::get_program_id(&mint_state))
+}
+
+/// Fee Token-2022 withholds from a transfer of `amount` in the current epoch, `None` for
+/// mints without the TransferFeeConfig extension.
+pub fn transfer_fee(mint: &AccountInfo, amount: u64) -> Result<Option<u64>> {
+    let data = mint.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<Token2022Mint>::unpack(&data)?;
+
+    let Ok(config) = mint_state.get_extension::<TransferFeeConfig>() else {
+        return Ok(None);
+    };
+    config
+        .calculate_epoch_fee(Clock::get()?.epoch, amount)
+        .map(Some)
+        .ok_or(error!(VaultError::Overflow))
+}
+
+/// Transfer fees withheld in `token_account`, 0 for accounts of mints without the
+/// TransferFeeConfig extension.
+pub fn withheld_transfer_fees(token_account: &AccountInfo) -> Result<u64> {
+    let data = token_account.try_borrow_data()?;
+    let account_state = StateWithExtensions::<Token2022Account>::unpack(&data)?;
+
+    Ok(account_state
+        .get_extension::<TransferFeeAmount>()
+        .map_or(0, |fee_amount| u64::from(fee_amount.withheld_amount)))
+}
+
+/// Extra accounts a transfer of `mint` needs: nothing when the mint has no transfer hook,
+/// otherwise the ExtraAccountMetaList, the source owner's whitelist PDA and the hook
+/// program. All three must be supplied, the program must be the mint's hook and the
+/// ExtraAccountMetaList its validation PDA; Token-2022 resolves the whitelist PDA from it.
+pub fn hook_accounts<'info>(
+    mint: &InterfaceAccount<'info, Mint>,
+    extra_account_meta_list: Option<&UncheckedAccount<'info>>,
+    source_owner_whitelist: Option<&UncheckedAccount<'info>>,
+    transfer_hook_program: Option<&UncheckedAccount<'info>>,
+) -> Result<Vec<AccountInfo<'info>>> {
+    let Some(hook_program_id) = transfer_hook_program_id(&mint.to_account_info())? else {
+        return Ok(Vec::new());
+    };
+
+    let (extra_account_meta_list_key, _) = Pubkey::find_program_address(
+        &[b"extra-account-metas", mint.key().as_ref()],
+        &hook_program_id,
+    );
+
+    match (extra_account_meta_list, source_owner_whitelist, transfer_hook_program) {
+        (Some(extra_account_meta_list), Some(whitelist), Some(program))
+            if program.key() == hook_program_id && extra_account_meta_list.key() == extra_account_meta_list_key =>
+        {
+            Ok(vec![
+                extra_account_meta_list.to_account_info(),
+                whitelist.to_account_info(),
+                program.to_account_info(),
+            ])
+        }
+        _ => err!(VaultError::MissingTransferHookAccounts),
+    }
+}
+
+/// Moves `amount` with `transfer_checked`, or with `transfer_checked_with_fee` when the
+/// mint charges a transfer fee, which then must not exceed `max_fee`. Returns the fee
+/// withheld from what `destination` receives.
+#[allow(clippy::too_many_arguments)]
+pub fn transfer_checked_with_max_fee<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    source: AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    destination: AccountInfo<'info>,
+    authority: AccountInfo<'info>,
+    hook_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    max_fee: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<u64> {
+    let Some(fee) = transfer_fee(&mint.to_account_info(), amount)? else {
+        onchain::invoke_transfer_checked(
+            &token_program.key(),
+            source,
+            mint.to_account_info(),
+            destination,
+            authority,
+            hook_accounts,
+            amount,
+            mint.decimals,
+            signer_seeds,
+        )?;
+        return Ok(0);
+    };
+    require!(fee <= max_fee, VaultError::TransferFeeExceedsMax);
+
+    onchain::invoke_transfer_checked_with_fee(
+        &token_program.key(),
+        source,
+        mint.to_account_info(),
+        destination,
+        authority,
+        hook_accounts,
+        amount,
+        mint.decimals,
+        fee,
+        signer_seeds,
+    )?;
+
+    Ok(fee)
+}
+
+/// Moves `amount` out of the vault reserve, signed by the vault PDA.
+///
+/// The vault is the owner of the source account, so for hooked mints the transfer hook
+/// resolves the vault's own whitelist entry: `hook_accounts` must hold the
+/// ExtraAccountMetaList, that whitelist PDA and the transfer hook program, see
+/// [`hook_accounts`]. It is empty for mints without a transfer hook.
+pub fn transfer_from_reserve<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    vault: &Account<'info, Vault>,
+    vault_token_reserve: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    destination: AccountInfo<'info>,
+    hook_accounts: &[AccountInfo<'info>],
+    amount: u64,
+) -> Result<()> {
+    transfer_from_reserve_with_max_fee(
+        token_program,
+        vault,
+        vault_token_reserve,
+        mint,
+        destination,
+        hook_accounts,
+        amount,
+        u64::MAX,
+    )?;
+
+    Ok(())
+}
+
+/// [`transfer_from_reserve`] for payouts where the recipient bounds the transfer fee it
+/// pays, returns the fee withheld.
+#[allow(clippy::too_many_arguments)]
+pub fn transfer_from_reserve_with_max_fee<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    vault: &Account<'info, Vault>,
+    vault_token_reserve: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    destination: AccountInfo<'info>,
+    hook_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    max_fee: u64,
+) -> Result<u64> {
+    let vault_index = vault.vault_index.to_le_bytes();
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"vault".as_ref(),
+        vault.creator.as_ref(),
+        vault.mint.as_ref(),
+        vault_index.as_ref(),
+        &[vault.bump],
+    ]];
+
+    transfer_checked_with_max_fee(
+        token_program,
+        vault_token_reserve.to_account_info(),
+        mint,
+        destination,
+        vault.to_account_info(),
+        hook_accounts,
+        amount,
+        max_fee,
+        signer_seeds,
+    )
+}
@@ -0,0 +1,12 @@
+use anchor_lang::prelude::*;
+
+/// Whether `whitelist` is the transfer hook's whitelist entry for `owner`, which only
+/// exists (and is owned by the hook program) once `owner` has been whitelisted.
+pub fn is_whitelisted(whitelist: &AccountInfo, transfer_hook_program: &Pubkey, mint: &Pubkey, owner: &Pubkey) -> bool {
+    let (expected, _) = Pubkey::find_program_address(
+        &[b"whitelist", mint.as_ref(), owner.as_ref()],
+        transfer_hook_program,
+    );
+
+    whitelist.key() == expected && whitelist.owner == transfer_hook_program && !whitelist.data_is_empty()
+}
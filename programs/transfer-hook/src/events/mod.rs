@@ -0,0 +1,17 @@
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct AddedToWhitelist {
+    pub address: Pubkey,
+    pub mint: Pubkey,
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RemovedFromWhitelist {
+    pub address: Pubkey,
+    pub mint: Pubkey,
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
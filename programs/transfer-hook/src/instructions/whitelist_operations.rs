@@ -1,7 +1,9 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token_interface::Mint;
+use crate::events::{AddedToWhitelist, RemovedFromWhitelist};
 use crate::state::Whitelist;
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct WhitelistOperations<'info> {
     #[account(mut)]
@@ -27,16 +29,28 @@ pub struct WhitelistOperations<'info> {
 }
 
 impl<'info> WhitelistOperations<'info> {
-    pub fn add_to_whitelist(&mut self, bump:u8) -> Result<()> {
+    pub fn add_to_whitelist(&mut self, bump:u8) -> Result<AddedToWhitelist> {
         self.whitelist_PDA.address = self.address.key();
         self.whitelist_PDA.mint = self.mint.key();
         self.whitelist_PDA.bump = bump;
 
         msg!("Whitelist initialized for address: {}", self.address.key());
-        Ok(())
+        Ok(AddedToWhitelist {
+            address: self.address.key(),
+            mint: self.mint.key(),
+            admin: self.admin.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        })
     }
 
-    pub fn remove_from_whitelist(&mut self) -> Result<()> {
-        self.whitelist_PDA.close(self.admin.to_account_info())
+    pub fn remove_from_whitelist(&mut self) -> Result<RemovedFromWhitelist> {
+        self.whitelist_PDA.close(self.admin.to_account_info())?;
+
+        Ok(RemovedFromWhitelist {
+            address: self.address.key(),
+            mint: self.mint.key(),
+            admin: self.admin.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        })
     }
 }
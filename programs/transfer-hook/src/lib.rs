@@ -15,6 +15,7 @@ use instructions::*;
 mod instructions;
 mod state;
 mod errors;
+mod events;
 
 use errors::*;
 
@@ -28,11 +29,15 @@ pub mod transfer_hook {
     use super::*;
     
     pub fn add_to_whitelist(ctx: Context<WhitelistOperations>) -> Result<()> {
-        ctx.accounts.add_to_whitelist(ctx.bumps.whitelist_PDA)
+        let event = ctx.accounts.add_to_whitelist(ctx.bumps.whitelist_PDA)?;
+        emit_cpi!(event);
+        Ok(())
     }
 
     pub fn remove_from_whitelist(ctx: Context<WhitelistOperations>) -> Result<()> {
-        ctx.accounts.remove_from_whitelist()
+        let event = ctx.accounts.remove_from_whitelist()?;
+        emit_cpi!(event);
+        Ok(())
     }
     
     #[instruction(discriminator = InitializeExtraAccountMetaListInstruction::SPL_DISCRIMINATOR_SLICE)]
@@ -56,6 +56,11 @@ mod tests {
 
     static PROGRAM_ID: Pubkey = crate::ID;
 
+    // PDA signing the self-CPI that carries emitted events
+    fn event_authority() -> Pubkey {
+        Pubkey::find_program_address(&[b"__event_authority"], &PROGRAM_ID).0
+    }
+
     fn setup() -> (LiteSVM, Keypair) {
         // Initialize LiteSVM and payer
         let mut program = LiteSVM::new();
@@ -94,7 +99,9 @@ mod tests {
             address: user.pubkey(),
             mint: mint.pubkey(),
             whitelist_PDA: whitelist_acc,
-            system_program : SYSTEM_PROGRAM_ID
+            system_program : SYSTEM_PROGRAM_ID,
+            event_authority: event_authority(),
+            program: PROGRAM_ID,
         };
 
         let ix = Instruction {
@@ -141,7 +148,9 @@ mod tests {
             address: user.pubkey(),
             mint: mint.pubkey(),
             whitelist_PDA: whitelist_acc,
-            system_program : SYSTEM_PROGRAM_ID
+            system_program : SYSTEM_PROGRAM_ID,
+            event_authority: event_authority(),
+            program: PROGRAM_ID,
         };
 
         let ix = Instruction {
@@ -176,7 +185,9 @@ mod tests {
             address: user.pubkey(),
             mint: mint.pubkey(),
             whitelist_PDA: whitelist_acc,
-            system_program : SYSTEM_PROGRAM_ID
+            system_program : SYSTEM_PROGRAM_ID,
+            event_authority: event_authority(),
+            program: PROGRAM_ID,
         };
 
         let ix = Instruction {